@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use crate::download::{download_file, DownloadOptions};
+
+/// 批量下载中单个 URL 的结果
+#[derive(Debug)]
+pub struct BatchResult {
+    pub url: String,
+    /// 成功时为保存的文件名，失败时为错误信息
+    pub outcome: Result<String, String>,
+}
+
+/// 从文件读取 URL 列表，`-` 表示标准输入
+pub fn read_url_list(path: &str) -> io::Result<Vec<String>> {
+    if path == "-" {
+        parse_url_list(io::stdin().lock())
+    } else {
+        parse_url_list(BufReader::new(File::open(path)?))
+    }
+}
+
+/// 每行一个 URL，忽略空行和以 `#` 开头的注释
+fn parse_url_list(reader: impl BufRead) -> io::Result<Vec<String>> {
+    let mut urls = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        urls.push(line.to_string());
+    }
+    Ok(urls)
+}
+
+/// 下载所有 URL，同时最多进行 `jobs` 个文件的传输；结果按输入顺序返回
+pub async fn download_all(urls: &[String], options: &DownloadOptions, jobs: u32) -> Vec<BatchResult> {
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1) as usize));
+    let mut tasks = JoinSet::new();
+
+    for (index, url) in urls.iter().enumerate() {
+        let semaphore = semaphore.clone();
+        let url = url.clone();
+        let options = options.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire().await.expect("semaphore closed");
+            let outcome = download_file(&url, &options).await.map_err(|e| e.to_string());
+            if let Err(e) = &outcome {
+                eprintln!("{} 下载失败: {}", url, e);
+            }
+            (index, outcome)
+        });
+    }
+
+    let mut outcomes: Vec<Option<Result<String, String>>> = vec![None; urls.len()];
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((index, outcome)) = joined {
+            outcomes[index] = Some(outcome);
+        }
+    }
+
+    urls.iter()
+        .zip(outcomes)
+        .map(|(url, outcome)| BatchResult {
+            url: url.clone(),
+            outcome: outcome.unwrap_or_else(|| Err("下载任务异常退出".to_string())),
+        })
+        .collect()
+}
+
+/// 打印每个 URL 的下载结果
+pub fn display_summary(results: &[BatchResult]) {
+    let succeeded = results.iter().filter(|r| r.outcome.is_ok()).count();
+    println!("\n下载汇总: 共 {} 个，成功 {} 个，失败 {} 个", results.len(), succeeded, results.len() - succeeded);
+    for result in results {
+        match &result.outcome {
+            Ok(filename) => println!("  [成功] {} -> {}", result.url, filename),
+            Err(e) => println!("  [失败] {}: {}", result.url, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::paths::PathLayout;
+    use crate::retry::RetryPolicy;
+    use crate::test_server::{closed_url, Route, TestServer};
+
+    #[test]
+    fn test_parse_url_list() {
+        let content = "# 发布产物\nhttps://example.com/a.zip\n\n  https://example.com/b.zip  \n#https://example.com/c.zip\n";
+        let urls = parse_url_list(content.as_bytes()).unwrap();
+        assert_eq!(urls, vec!["https://example.com/a.zip", "https://example.com/b.zip"]);
+    }
+
+    #[test]
+    fn test_read_url_list_missing_file() {
+        assert!(read_url_list("/tmp/wget-rs-no-such-list.txt").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_all_keeps_input_order() {
+        let options = DownloadOptions {
+            threads: 1,
+            retry: RetryPolicy { tries: 1, ..RetryPolicy::default() },
+            ..DownloadOptions::default()
+        };
+        // 本机未监听的端口，连接会立即失败
+        let urls = vec![closed_url("/first"), closed_url("/second")];
+        let results = download_all(&urls, &options, 2).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, urls[0]);
+        assert_eq!(results[1].url, urls[1]);
+        assert!(results.iter().all(|r| r.outcome.is_err()));
+    }
+
+    #[tokio::test]
+    async fn test_same_basename_downloads_do_not_collide() {
+        let first: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+        let second: Vec<u8> = (0..256 * 1024).map(|i| (i % 241) as u8).collect();
+        // 响应前稍作等待，保证两个任务同时进行
+        let server = TestServer::start(vec![
+            ("/big.bin", Route::file(first.clone()).delay(Duration::from_millis(50))),
+            ("/copy/big.bin", Route::file(second.clone()).delay(Duration::from_millis(50))),
+        ]).await;
+
+        let dir = PathBuf::from("/tmp/test_wget_rs_same_basename");
+        std::fs::remove_dir_all(&dir).ok();
+        let options = DownloadOptions {
+            threads: 4,
+            min_split_size: 16 * 1024,
+            retry: RetryPolicy { tries: 1, ..RetryPolicy::default() },
+            layout: PathLayout { prefix: Some(dir.clone()), ..PathLayout::default() },
+            ..DownloadOptions::default()
+        };
+        let urls = vec![server.url("/big.bin"), server.url("/copy/big.bin")];
+        let results = download_all(&urls, &options, 2).await;
+
+        let saved: Vec<String> = results.into_iter().map(|r| r.outcome.unwrap()).collect();
+        assert_ne!(saved[0], saved[1]);
+        let mut names: Vec<&str> = saved.iter().map(|name| name.rsplit('/').next().unwrap()).collect();
+        names.sort();
+        assert_eq!(names, vec!["big.bin", "big.bin.1"]);
+        assert_eq!(std::fs::read(&saved[0]).unwrap(), first);
+        assert_eq!(std::fs::read(&saved[1]).unwrap(), second);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
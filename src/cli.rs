@@ -1,4 +1,8 @@
-use clap::Parser;
+use std::ffi::OsString;
+use clap::{Parser, ValueEnum};
+use reqwest::{Method, Url};
+use reqwest::header::{HeaderName, HeaderValue};
+use crate::proxy::parse_proxy_url;
 
 /// wget-rs：一个现代 Rust 版多线程命令行下载器
 #[derive(Parser, Debug)]
@@ -13,6 +17,10 @@ use clap::Parser;
 
 - 支持多线程高速下载（可指定线程数）
 - 支持断点续传（使用 -c 选项启用）
+- 支持从文件批量下载（-i），并可同时下载多个文件（-j）
+- 支持只检查链接是否有效而不下载（--spider）
+- 支持递归下载网站（-r），可限制深度和范围；镜像模式（-m）只更新有修改的文件
+- 支持下载页面所需资源（-p）并转换链接以便离线浏览（-k）
 - 支持自动推断文件名
 - 支持下载完成后文件哈希计算与校验（MD5/SHA1/SHA256/CRC32）
 - 兼容 http/https
@@ -24,13 +32,95 @@ use clap::Parser;
 "#
 )]
 pub struct Args {
-    /// 要下载的 URL
-    pub url: String,
-    /// 输出文件名（可选，默认从服务器获取或URL推断）
-    #[arg(short, long)]
+    /// 要下载的 URL，可以有多个
+    #[arg(required_unless_present = "input_file")]
+    pub urls: Vec<String>,
+    /// 从文件中读取要下载的 URL（每行一个，`-` 表示标准输入）
+    #[arg(short, long, value_name = "FILE")]
+    pub input_file: Option<String>,
+    /// 批量下载时同时下载的文件数，每个文件仍按 --threads 分段
+    #[arg(short = 'j', long, default_value = "5", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_concurrent_downloads: u32,
+    /// 输出文件名（可选，默认从服务器获取或URL推断），`-` 表示写到标准输出
+    #[arg(short = 'O', long, short_alias = 'o', value_name = "FILE")]
     pub output: Option<String>,
-    /// 线程数（默认32）
-    #[arg(short, long, default_value = "32")]
+    /// 安静模式：不显示进度条和过程信息，只输出错误
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// 把文件保存到这个目录下，目录不存在时自动创建
+    #[arg(short = 'P', long, value_name = "DIR")]
+    pub directory_prefix: Option<String>,
+    /// 非递归下载时也按 主机名/路径 创建目录
+    #[arg(short = 'x', long)]
+    pub force_directories: bool,
+    /// 不创建主机名目录（也可以写作 -nH）
+    #[arg(long)]
+    pub no_host_directories: bool,
+    /// 创建目录时忽略 URL 路径中的前 N 级目录
+    #[arg(long, default_value = "0", value_name = "N")]
+    pub cut_dirs: usize,
+    /// 目标文件已存在时跳过下载，而不是保存为 文件名.1（也可以写作 -nc）
+    #[arg(long, conflicts_with_all = ["timestamping", "mirror", "continue_", "backups"])]
+    pub no_clobber: bool,
+    /// 覆盖已存在的文件前把旧版本轮换为 文件名.1 到 文件名.N
+    #[arg(long, default_value = "0", value_name = "N")]
+    pub backups: u32,
+    /// 附加到每个请求的请求头，格式为 "名称: 值"，可以重复使用
+    #[arg(long, value_name = "HEADER", value_parser = parse_header)]
+    pub header: Vec<(HeaderName, HeaderValue)>,
+    /// 替换默认的 User-Agent（Wget/版本 (系统)）
+    #[arg(short = 'U', long, value_name = "AGENT")]
+    pub user_agent: Option<String>,
+    /// 请求中附带的 Referer
+    #[arg(long, value_name = "URL")]
+    pub referer: Option<String>,
+    /// 使用指定的 HTTP 方法（例如 POST、PUT、DELETE）发送请求，并保存响应
+    #[arg(long, value_name = "METHOD", value_parser = parse_method)]
+    pub method: Option<Method>,
+    /// 用 POST 方法发送这段数据，并保存响应
+    #[arg(long, value_name = "STRING", conflicts_with_all = ["post_file", "method", "body_data", "body_file"])]
+    pub post_data: Option<String>,
+    /// 用 POST 方法发送文件内容，并保存响应
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["method", "body_data", "body_file"])]
+    pub post_file: Option<String>,
+    /// 与 --method 一起使用，作为请求体发送的数据
+    #[arg(long, value_name = "STRING", requires = "method", conflicts_with = "body_file")]
+    pub body_data: Option<String>,
+    /// 与 --method 一起使用，作为请求体发送的文件
+    #[arg(long, value_name = "FILE", requires = "method")]
+    pub body_file: Option<String>,
+    /// 从 Netscape 格式的 cookies.txt 读取 cookie
+    #[arg(long, value_name = "FILE")]
+    pub load_cookies: Option<String>,
+    /// 结束时把 cookie 保存为 Netscape 格式的 cookies.txt
+    #[arg(long, value_name = "FILE")]
+    pub save_cookies: Option<String>,
+    /// 保存 cookie 时也保存会话 cookie（没有过期时间的 cookie）
+    #[arg(long, requires = "save_cookies")]
+    pub keep_session_cookies: bool,
+    /// HTTP 认证的用户名；没有指定时按主机查找 ~/.netrc
+    #[arg(long)]
+    pub user: Option<String>,
+    /// HTTP 认证的密码
+    #[arg(long)]
+    pub password: Option<String>,
+    /// 运行时提示输入密码，避免密码出现在命令行历史中
+    #[arg(long, conflicts_with = "password")]
+    pub ask_password: bool,
+    /// 所有请求使用的代理，例如 http://proxy:3128、socks5h://127.0.0.1:1080；默认读取 http_proxy/https_proxy
+    #[arg(long, value_name = "URL", value_parser = parse_proxy_url)]
+    pub proxy: Option<Url>,
+    /// 代理认证的用户名
+    #[arg(long, value_name = "USER")]
+    pub proxy_user: Option<String>,
+    /// 代理认证的密码
+    #[arg(long, value_name = "PASSWORD")]
+    pub proxy_password: Option<String>,
+    /// 不使用任何代理，忽略 http_proxy 等环境变量
+    #[arg(long, conflicts_with_all = ["proxy", "proxy_user", "proxy_password"])]
+    pub no_proxy: bool,
+    /// 并发连接数（默认32）
+    #[arg(short, long, default_value = "32", value_parser = clap::value_parser!(u32).range(1..))]
     pub threads: u32,
     /// 断点续传下载文件
     #[arg(short, long)]
@@ -41,10 +131,184 @@ pub struct Args {
     /// 验证下载文件的哈希值（格式：MD5、SHA1、SHA256或CRC32）
     #[arg(long, value_name = "HASH")]
     pub verify_hash: Option<String>,
+    /// 每个文件（或区段）的最大尝试次数，包含第一次请求
+    #[arg(long, default_value = "5", value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub tries: u32,
+    /// 第一次重试前等待的秒数，之后每次重试等待时间翻倍
+    #[arg(long, default_value = "1", value_name = "SECONDS")]
+    pub retry_wait: u64,
+    /// 重试等待时间的上限（秒）
+    #[arg(long, default_value = "30", value_name = "SECONDS")]
+    pub max_retry_wait: u64,
+    /// 每个区段的最小大小，首次分段和拆分慢速区段时都不会小于它，支持 k/M/G 后缀
+    #[arg(long, default_value = "1M", value_name = "SIZE", value_parser = parse_size)]
+    pub min_split_size: u64,
+    /// 续传时如果远程文件已改变（ETag/Last-Modified 不一致）则报错退出，而不是重新下载
+    #[arg(long)]
+    pub strict_resume: bool,
+    /// 多线程下载时输出文件的预分配方式
+    #[arg(long, value_enum, default_value = "falloc")]
+    pub file_allocation: FileAllocation,
+    /// 限制总下载速度（字节/秒），所有线程共享，支持 k/M/G 后缀，例如 2M
+    #[arg(long, value_name = "RATE", value_parser = parse_size)]
+    pub limit_rate: Option<u64>,
+    /// 只检查 URL 是否可以访问（状态码、最终地址、大小），不保存文件；有失效链接时以非零状态退出
+    #[arg(long, conflicts_with_all = ["recursive", "mirror", "page_requisites"])]
+    pub spider: bool,
+    /// 递归下载：解析 HTML 页面中的链接并继续下载，按网站目录结构保存
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// 递归下载的最大深度，0 或 inf 表示不限制
+    #[arg(short, long, default_value = "5", value_name = "N", value_parser = parse_level)]
+    pub level: u32,
+    /// 递归下载时不进入起始目录的上级目录（页面所需的资源除外）
+    #[arg(long)]
+    pub no_parent: bool,
+    /// 递归下载时允许跟随指向其他主机的链接
+    #[arg(short = 'H', long)]
+    pub span_hosts: bool,
+    /// 时间戳模式：只下载比本地副本新的文件
+    #[arg(short = 'N', long)]
+    pub timestamping: bool,
+    /// 镜像模式：相当于 -r -l inf -N，保持本地副本与网站同步
+    #[arg(short, long)]
+    pub mirror: bool,
+    /// 下载显示页面所需的图片、样式表、脚本和字体（包括 CSS 中 url() 引用的资源）
+    #[arg(short, long)]
+    pub page_requisites: bool,
+    /// 下载完成后把 HTML/CSS 中的链接改写为指向本地副本，便于离线浏览
+    #[arg(short = 'k', long)]
+    pub convert_links: bool,
+    /// 只下载文件名匹配的文件，逗号分隔的后缀或通配符，例如 pdf,*.tar.gz
+    #[arg(short = 'A', long, value_name = "LIST", value_delimiter = ',')]
+    pub accept: Vec<String>,
+    /// 不下载文件名匹配的文件，逗号分隔的后缀或通配符
+    #[arg(short = 'R', long, value_name = "LIST", value_delimiter = ',')]
+    pub reject: Vec<String>,
+    /// 只下载完整 URL 匹配该正则表达式的文件
+    #[arg(long, value_name = "REGEX")]
+    pub accept_regex: Option<String>,
+    /// 不下载完整 URL 匹配该正则表达式的文件
+    #[arg(long, value_name = "REGEX")]
+    pub reject_regex: Option<String>,
+    /// 只下载这些域名（包括子域名）下的文件，逗号分隔
+    #[arg(short = 'D', long, value_name = "LIST", value_delimiter = ',')]
+    pub domains: Vec<String>,
+    /// 不下载这些域名（包括子域名）下的文件，逗号分隔
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    pub exclude_domains: Vec<String>,
+    /// 只下载这些目录（包括子目录）下的文件，逗号分隔，支持通配符
+    #[arg(short = 'I', long, value_name = "LIST", value_delimiter = ',')]
+    pub include_directories: Vec<String>,
+    /// 不下载这些目录（包括子目录）下的文件，逗号分隔，支持通配符
+    #[arg(short = 'X', long, value_name = "LIST", value_delimiter = ',')]
+    pub exclude_directories: Vec<String>,
+    /// 执行 wgetrc 风格的命令，可以重复使用，例如 -e robots=off
+    #[arg(short = 'e', long, value_name = "COMMAND", value_parser = parse_command)]
+    pub execute: Vec<Command>,
+}
+
+/// 输出文件的预分配方式
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum FileAllocation {
+    /// 不预分配，文件随写入增长
+    None,
+    /// 只设置文件长度（稀疏文件）
+    Trunc,
+    /// 使用 fallocate 等系统调用真正分配磁盘空间，减少碎片
+    Falloc,
+}
+
+/// `-e` 支持的 wgetrc 命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `robots=on|off`：递归下载时是否遵守 robots.txt 和 `<meta name="robots">`
+    Robots(bool),
+}
+
+/// 解析 `名称=值` 形式的命令，名称不区分大小写，忽略其中的 `-` 和 `_`
+pub fn parse_command(value: &str) -> Result<Command, String> {
+    let (name, setting) = value
+        .split_once('=')
+        .ok_or_else(|| format!("无效的命令: {}，格式应为 名称=值", value))?;
+    let name: String = name.trim().to_ascii_lowercase().chars().filter(|c| *c != '-' && *c != '_').collect();
+    match name.as_str() {
+        "robots" => parse_switch(setting).map(Command::Robots),
+        _ => Err(format!("不支持的命令: {}", name)),
+    }
+}
+
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "on" | "yes" | "true" | "1" => Ok(true),
+        "off" | "no" | "false" | "0" => Ok(false),
+        other => Err(format!("无效的开关值: {}，应为 on 或 off", other)),
+    }
+}
+
+/// 解析 `名称: 值` 形式的请求头
+pub fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, header_value) = value
+        .split_once(':')
+        .ok_or_else(|| format!("无效的请求头: {}，格式应为 \"名称: 值\"", value))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| format!("无效的请求头名称: {}", name.trim()))?;
+    let header_value = HeaderValue::from_str(header_value.trim()).map_err(|_| format!("无效的请求头值: {}", header_value.trim()))?;
+    Ok((name, header_value))
+}
+
+/// 解析 HTTP 方法，不区分大小写
+pub fn parse_method(value: &str) -> Result<Method, String> {
+    Method::from_bytes(value.trim().to_ascii_uppercase().as_bytes()).map_err(|_| format!("无效的 HTTP 方法: {}", value))
+}
+
+/// 解析递归深度，`inf` 与 0 一样表示不限制
+pub fn parse_level(value: &str) -> Result<u32, String> {
+    match value.trim() {
+        "inf" | "infinite" => Ok(0),
+        level => level.parse().map_err(|_| format!("无效的递归深度: {}", value)),
+    }
+}
+
+/// 解析带 k/M/G 后缀（以 1024 为进制）的字节数，例如 `512k`、`2M`
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last() {
+        Some('k') | Some('K') => (&value[..value.len() - 1], 1024),
+        Some('m') | Some('M') => (&value[..value.len() - 1], 1024 * 1024),
+        Some('g') | Some('G') => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("无效的大小: {}", value))?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(format!("无效的大小: {}", value));
+    }
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// wget 中由两个字母组成的短选项，clap 无法直接表示，解析前换成对应的长选项
+const WGET_SHORT_OPTIONS: &[(&str, &str)] = &[("-nH", "--no-host-directories"), ("-nc", "--no-clobber")];
+
+/// 把命令行中 wget 风格的短选项替换为长选项，`--` 之后的参数保持不变
+pub fn expand_wget_options(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut options_ended = false;
+    args.into_iter()
+        .map(|arg| {
+            if options_ended {
+                return arg;
+            }
+            options_ended = arg == "--";
+            match WGET_SHORT_OPTIONS.iter().find(|(short, _)| arg == *short) {
+                Some((_, long)) => OsString::from(long),
+                None => arg,
+            }
+        })
+        .collect()
 }
 
 pub fn parse_args() -> Args {
-    Args::parse()
+    Args::parse_from(expand_wget_options(std::env::args_os()))
 }
 
 #[cfg(test)]
@@ -54,25 +318,193 @@ mod tests {
     #[test]
     fn test_default_threads() {
         let args = Args {
-            url: "https://example.com".to_string(),
+            urls: vec!["https://example.com".to_string()],
+            input_file: None,
+            max_concurrent_downloads: 5,
             output: None,
+            quiet: false,
+            directory_prefix: None,
+            force_directories: false,
+            no_host_directories: false,
+            cut_dirs: 0,
+            no_clobber: false,
+            backups: 0,
+            header: vec![],
+            user_agent: None,
+            referer: None,
+            method: None,
+            post_data: None,
+            post_file: None,
+            body_data: None,
+            body_file: None,
+            load_cookies: None,
+            save_cookies: None,
+            keep_session_cookies: false,
+            user: None,
+            password: None,
+            ask_password: false,
+            proxy: None,
+            proxy_user: None,
+            proxy_password: None,
+            no_proxy: false,
             threads: 32,
             continue_: false,
             hash: false,
             verify_hash: None,
+            tries: 5,
+            retry_wait: 1,
+            max_retry_wait: 30,
+            min_split_size: 1024 * 1024,
+            strict_resume: false,
+            file_allocation: FileAllocation::Falloc,
+            limit_rate: None,
+            spider: false,
+            recursive: false,
+            level: 5,
+            no_parent: false,
+            span_hosts: false,
+            timestamping: false,
+            mirror: false,
+            page_requisites: false,
+            convert_links: false,
+            accept: vec![],
+            reject: vec![],
+            accept_regex: None,
+            reject_regex: None,
+            domains: vec![],
+            exclude_domains: vec![],
+            include_directories: vec![],
+            exclude_directories: vec![],
+            execute: vec![],
         };
         assert_eq!(args.threads, 32);
     }
 
+    #[test]
+    fn test_threads_must_be_positive() {
+        assert!(Args::try_parse_from(["wget-rs", "-t", "0", "https://example.com/"]).is_err());
+        assert_eq!(Args::try_parse_from(["wget-rs", "-t", "4", "https://example.com/"]).unwrap().threads, 4);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("512k"), Ok(512 * 1024));
+        assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size("1.5K"), Ok(1536));
+        assert_eq!(parse_size("1G"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("abc").is_err());
+        assert!(parse_size("-1M").is_err());
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("3"), Ok(3));
+        assert_eq!(parse_level("0"), Ok(0));
+        assert_eq!(parse_level("inf"), Ok(0));
+        assert!(parse_level("-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let (name, value) = parse_header("X-Api-Key:  secret ").unwrap();
+        assert_eq!(name, "x-api-key");
+        assert_eq!(value, "secret");
+        let (_, value) = parse_header("Accept: text/html, */*; q=0.1").unwrap();
+        assert_eq!(value, "text/html, */*; q=0.1");
+        assert!(parse_header("X-Api-Key").is_err());
+        assert!(parse_header("Bad Name: x").is_err());
+    }
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!(parse_method("post"), Ok(Method::POST));
+        assert_eq!(parse_method("PROPFIND").unwrap().as_str(), "PROPFIND");
+        assert!(parse_method("GET /").is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("robots=off"), Ok(Command::Robots(false)));
+        assert_eq!(parse_command("Robots = On"), Ok(Command::Robots(true)));
+        assert!(parse_command("robots").is_err());
+        assert!(parse_command("robots=maybe").is_err());
+        assert!(parse_command("tries=3").is_err());
+    }
+
+    #[test]
+    fn test_expand_wget_options() {
+        let args: Vec<OsString> = ["wget-rs", "-nH", "--cut-dirs", "1", "--", "-nH"].iter().map(OsString::from).collect();
+        let expanded = expand_wget_options(args);
+        assert_eq!(expanded, ["wget-rs", "--no-host-directories", "--cut-dirs", "1", "--", "-nH"]);
+
+        let args = Args::parse_from(expand_wget_options(["wget-rs", "-nH", "-P", "out", "https://example.com/"].map(OsString::from)));
+        assert!(args.no_host_directories);
+        assert!(!args.span_hosts);
+        assert_eq!(args.directory_prefix.as_deref(), Some("out"));
+    }
+
     #[test]
     fn test_continue_flag() {
         let args = Args {
-            url: "https://example.com/file.txt".to_string(),
+            urls: vec!["https://example.com/file.txt".to_string()],
+            input_file: None,
+            max_concurrent_downloads: 5,
             output: Some("downloaded_file.txt".to_string()),
+            quiet: false,
+            directory_prefix: None,
+            force_directories: false,
+            no_host_directories: false,
+            cut_dirs: 0,
+            no_clobber: false,
+            backups: 0,
+            header: vec![],
+            user_agent: None,
+            referer: None,
+            method: None,
+            post_data: None,
+            post_file: None,
+            body_data: None,
+            body_file: None,
+            load_cookies: None,
+            save_cookies: None,
+            keep_session_cookies: false,
+            user: None,
+            password: None,
+            ask_password: false,
+            proxy: None,
+            proxy_user: None,
+            proxy_password: None,
+            no_proxy: false,
             threads: 4,
             continue_: true,
             hash: false,
             verify_hash: None,
+            tries: 5,
+            retry_wait: 1,
+            max_retry_wait: 30,
+            min_split_size: 1024 * 1024,
+            strict_resume: false,
+            file_allocation: FileAllocation::Falloc,
+            limit_rate: None,
+            spider: false,
+            recursive: false,
+            level: 5,
+            no_parent: false,
+            span_hosts: false,
+            timestamping: false,
+            mirror: false,
+            page_requisites: false,
+            convert_links: false,
+            accept: vec![],
+            reject: vec![],
+            accept_regex: None,
+            reject_regex: None,
+            domains: vec![],
+            exclude_domains: vec![],
+            include_directories: vec![],
+            exclude_directories: vec![],
+            execute: vec![],
         };
         assert!(args.continue_);
         assert_eq!(args.output, Some("downloaded_file.txt".to_string()));
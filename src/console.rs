@@ -0,0 +1,23 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `-q` 时不显示进度条和过程信息，只保留错误
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// 输出过程信息；统一写到标准错误，保证 `-O -` 时标准输出只有文件内容
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !$crate::console::is_quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use info;
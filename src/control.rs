@@ -0,0 +1,286 @@
+use std::fs;
+use std::io;
+
+/// 控制文件的扩展名，与输出文件放在同一目录下
+const CONTROL_FILE_EXTENSION: &str = "wget-rs";
+
+/// 多线程下载中的一个区段，`[start, start + downloaded)` 范围内的字节已写入文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: u64,
+    pub end: u64,
+    pub downloaded: u64,
+}
+
+impl Segment {
+    pub fn new(start: u64, end: u64) -> Self {
+        Segment { start, end, downloaded: 0 }
+    }
+
+    /// 区段总长度（包含 end）
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// 下一个待下载字节的位置
+    pub fn position(&self) -> u64 {
+        self.start + self.downloaded
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.downloaded >= self.len()
+    }
+}
+
+/// `If-Range` 使用的校验值：优先使用强 ETag，否则使用 Last-Modified；弱 ETag 不能用于 `If-Range`
+pub fn if_range_validator<'a>(etag: Option<&'a str>, last_modified: Option<&'a str>) -> Option<&'a str> {
+    etag.filter(|etag| !etag.starts_with("W/")).or(last_modified)
+}
+
+/// 将文件按线程数切分为首尾相接的区段，最后一段包含余数
+pub fn plan_segments(total_size: u64, threads: u32) -> Vec<Segment> {
+    let chunk_size = total_size / threads as u64;
+    (0..threads)
+        .map(|i| {
+            let start = i as u64 * chunk_size;
+            let end = if i == threads - 1 {
+                total_size - 1
+            } else {
+                (i + 1) as u64 * chunk_size - 1
+            };
+            Segment::new(start, end)
+        })
+        .collect()
+}
+
+/// 断点续传控制文件，记录下载来源与各区段的完成进度
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFile {
+    pub url: String,
+    pub total_size: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub segments: Vec<Segment>,
+}
+
+impl ControlFile {
+    /// 获取输出文件对应的控制文件路径
+    pub fn path_for(filename: &str) -> String {
+        format!("{}.{}", filename, CONTROL_FILE_EXTENSION)
+    }
+
+    /// 已下载的总字节数
+    pub fn downloaded(&self) -> u64 {
+        self.segments.iter().map(|s| s.downloaded).sum()
+    }
+
+    /// 已记录的进度都落在现有文件范围内时，才能信任控制文件继续下载
+    pub fn fits_file(&self, file_size: u64) -> bool {
+        self.segments
+            .iter()
+            .all(|s| s.downloaded == 0 || s.position() <= file_size)
+    }
+
+    /// 续传请求中 `If-Range` 使用的校验值，见 [`if_range_validator`]
+    pub fn if_range(&self) -> Option<&str> {
+        if_range_validator(self.etag.as_deref(), self.last_modified.as_deref())
+    }
+
+    /// 判断控制文件是否描述的是同一个远程资源
+    pub fn matches(&self, url: &str, total_size: u64, etag: Option<&str>, last_modified: Option<&str>) -> bool {
+        fn same(saved: &Option<String>, current: Option<&str>) -> bool {
+            match (saved.as_deref(), current) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+        }
+
+        self.url == url
+            && self.total_size == total_size
+            && same(&self.etag, etag)
+            && same(&self.last_modified, last_modified)
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// 先写入临时文件再重命名，避免中断时留下不完整的控制文件
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, self.serialize())?;
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn remove(path: &str) {
+        fs::remove_file(path).ok();
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = String::from("# wget-rs control file\n");
+        content.push_str(&format!("url={}\n", self.url));
+        content.push_str(&format!("total_size={}\n", self.total_size));
+        if let Some(etag) = &self.etag {
+            content.push_str(&format!("etag={}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            content.push_str(&format!("last_modified={}\n", last_modified));
+        }
+        for segment in &self.segments {
+            content.push_str(&format!("segment={}-{}:{}\n", segment.start, segment.end, segment.downloaded));
+        }
+        content
+    }
+
+    fn parse(content: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut url = None;
+        let mut total_size = None;
+        let mut etag = None;
+        let mut last_modified = None;
+        let mut segments = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("控制文件格式错误: {}", line))?;
+            match key {
+                "url" => url = Some(value.to_string()),
+                "total_size" => total_size = Some(value.parse::<u64>()?),
+                "etag" => etag = Some(value.to_string()),
+                "last_modified" => last_modified = Some(value.to_string()),
+                "segment" => segments.push(parse_segment(value)?),
+                _ => {}
+            }
+        }
+
+        let segment_total: u64 = segments.iter().map(Segment::len).sum();
+        let total_size = total_size.ok_or("控制文件缺少 total_size")?;
+        if segments.is_empty() || segment_total != total_size {
+            return Err("控制文件中的区段信息与文件大小不一致".into());
+        }
+
+        Ok(ControlFile {
+            url: url.ok_or("控制文件缺少 url")?,
+            total_size,
+            etag,
+            last_modified,
+            segments,
+        })
+    }
+}
+
+fn parse_segment(value: &str) -> Result<Segment, Box<dyn std::error::Error + Send + Sync>> {
+    let (range, downloaded) = value
+        .split_once(':')
+        .ok_or_else(|| format!("区段格式错误: {}", value))?;
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("区段格式错误: {}", value))?;
+
+    let segment = Segment {
+        start: start.parse()?,
+        end: end.parse()?,
+        downloaded: downloaded.parse()?,
+    };
+    if segment.end < segment.start || segment.downloaded > segment.len() {
+        return Err(format!("区段范围无效: {}", value).into());
+    }
+    Ok(segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ControlFile {
+        ControlFile {
+            url: "https://example.com/file.iso".to_string(),
+            total_size: 1000,
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            segments: vec![
+                Segment { start: 0, end: 499, downloaded: 500 },
+                Segment { start: 500, end: 999, downloaded: 120 },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_plan_segments() {
+        let segments = plan_segments(1000, 3);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], Segment::new(0, 332));
+        assert_eq!(segments[1], Segment::new(333, 665));
+        assert_eq!(segments[2], Segment::new(666, 999));
+        assert_eq!(segments.iter().map(Segment::len).sum::<u64>(), 1000);
+    }
+
+    #[test]
+    fn test_serialize_roundtrip() {
+        let control = sample();
+        let parsed = ControlFile::parse(&control.serialize()).unwrap();
+        assert_eq!(parsed, control);
+        assert_eq!(parsed.downloaded(), 620);
+        assert!(parsed.segments[0].is_complete());
+        assert!(!parsed.segments[1].is_complete());
+        assert_eq!(parsed.segments[1].position(), 620);
+    }
+
+    #[test]
+    fn test_parse_rejects_inconsistent_segments() {
+        let content = "url=https://example.com/a\ntotal_size=100\nsegment=0-49:0\n";
+        assert!(ControlFile::parse(content).is_err());
+
+        let content = "url=https://example.com/a\ntotal_size=100\nsegment=0-99:101\n";
+        assert!(ControlFile::parse(content).is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let control = sample();
+        assert!(control.matches("https://example.com/file.iso", 1000, Some("\"abc\""), None));
+        assert!(!control.matches("https://example.com/file.iso", 1000, Some("\"def\""), None));
+        assert!(!control.matches("https://example.com/file.iso", 2000, None, None));
+        assert!(!control.matches("https://example.com/other.iso", 1000, None, None));
+    }
+
+    #[test]
+    fn test_fits_file() {
+        let control = sample();
+        assert!(control.fits_file(1000));
+        assert!(control.fits_file(620));
+        assert!(!control.fits_file(600));
+    }
+
+    #[test]
+    fn test_if_range() {
+        let mut control = sample();
+        assert_eq!(control.if_range(), Some("\"abc\""));
+
+        // 弱 ETag 不能用于 If-Range
+        control.etag = Some("W/\"abc\"".to_string());
+        assert_eq!(control.if_range(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+
+        control.last_modified = None;
+        assert_eq!(control.if_range(), None);
+
+        // `-O -` 直接使用响应头中的值
+        assert_eq!(if_range_validator(Some("\"v2\""), Some("Wed, 21 Oct 2015 07:28:00 GMT")), Some("\"v2\""));
+        assert_eq!(if_range_validator(Some("W/\"v2\""), None), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = "/tmp/test_control_file.wget-rs";
+        let control = sample();
+        control.save(path).unwrap();
+        assert_eq!(ControlFile::load(path).unwrap(), control);
+        ControlFile::remove(path);
+        assert!(ControlFile::load(path).is_err());
+    }
+}
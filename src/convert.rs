@@ -0,0 +1,118 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use reqwest::Url;
+use crate::html::{document_base, extract_css_links, extract_links, resolve, rewrite_links, LinkKind};
+
+/// 需要转换链接的文档类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentKind {
+    Html,
+    Css,
+}
+
+/// 改写已下载文档中的链接，使其可以离线浏览
+///
+/// `local_path` 返回已下载资源的本地路径；指向已下载资源的链接改为相对路径，
+/// 其余链接改为绝对地址，仍然可以在线访问。返回文档是否有改动。
+pub fn convert_document(
+    path: &Path,
+    url: &Url,
+    kind: DocumentKind,
+    local_path: impl Fn(&Url) -> Option<PathBuf>,
+) -> io::Result<bool> {
+    // 非 UTF-8 编码的文档无法安全地原样改写，保持不变
+    let Ok(content) = String::from_utf8(std::fs::read(path)?) else {
+        return Ok(false);
+    };
+
+    let (links, base) = match kind {
+        DocumentKind::Html => {
+            let links = extract_links(&content);
+            let base = document_base(url, &links);
+            (links, base)
+        }
+        DocumentKind::Css => (extract_css_links(&content), url.clone()),
+    };
+
+    let converted = rewrite_links(&content, &links, |link| {
+        // 链接都已改写为相对于文档自身的路径，`<base>` 需要指向文档所在目录
+        if link.kind == LinkKind::Base {
+            return Some("./".to_string());
+        }
+        let target = resolve(&base, &link.value)?;
+        let fragment = link.value.find('#').map_or("", |i| &link.value[i..]);
+        match local_path(&target) {
+            Some(target_path) => Some(format!("{}{}", relative_link(path, &target_path), fragment)),
+            None => Some(format!("{}{}", target, fragment)),
+        }
+    });
+
+    if converted == content {
+        return Ok(false);
+    }
+    std::fs::write(path, converted)?;
+    Ok(true)
+}
+
+/// 从文档 `from` 指向本地文件 `to` 的相对链接
+fn relative_link(from: &Path, to: &Path) -> String {
+    let from_dir: Vec<_> = from.parent().map(|dir| dir.components().collect()).unwrap_or_default();
+    let to: Vec<_> = to.components().collect();
+    let common = from_dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec!["..".to_string(); from_dir.len() - common];
+    parts.extend(to[common..].iter().map(|c| encode_link_component(&c.as_os_str().to_string_lossy())));
+    parts.join("/")
+}
+
+/// 转义在链接中有特殊含义的字符，例如保存查询字符串的文件名中的 `?`
+fn encode_link_component(component: &str) -> String {
+    component
+        .replace('%', "%25")
+        .replace('?', "%3F")
+        .replace('#', "%23")
+        .replace(' ', "%20")
+        .replace('"', "%22")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::PathLayout;
+
+    #[test]
+    fn test_relative_link() {
+        let from = Path::new("example.com/docs/guide/index.html");
+        assert_eq!(relative_link(from, Path::new("example.com/docs/guide/intro.html")), "intro.html");
+        assert_eq!(relative_link(from, Path::new("example.com/img/logo.png")), "../../img/logo.png");
+        assert_eq!(relative_link(from, Path::new("cdn.example.com/app.js")), "../../../cdn.example.com/app.js");
+        assert_eq!(relative_link(from, Path::new("example.com/search?q=a b")), "../../search%3Fq=a%20b");
+    }
+
+    #[test]
+    fn test_convert_document() {
+        let dir = Path::new("/tmp/test_convert_document/example.com/docs");
+        std::fs::create_dir_all(dir).unwrap();
+        let page = dir.join("index.html");
+        std::fs::write(
+            &page,
+            r#"<a href="intro.html#usage">Intro</a><img src="/logo.png"><a href="/missing.html">Missing</a>"#,
+        ).unwrap();
+
+        let url = Url::parse("https://example.com/docs/index.html").unwrap();
+        let downloaded = [
+            Url::parse("https://example.com/docs/intro.html").unwrap(),
+            Url::parse("https://example.com/logo.png").unwrap(),
+        ];
+        let changed = convert_document(&page, &url, DocumentKind::Html, |target| {
+            downloaded.contains(target).then(|| Path::new("/tmp/test_convert_document").join(PathLayout::default().local_path(target)))
+        }).unwrap();
+
+        assert!(changed);
+        assert_eq!(
+            std::fs::read_to_string(&page).unwrap(),
+            r#"<a href="intro.html#usage">Intro</a><img src="../logo.png"><a href="https://example.com/missing.html">Missing</a>"#
+        );
+        std::fs::remove_dir_all("/tmp/test_convert_document").ok();
+    }
+}
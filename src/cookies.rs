@@ -0,0 +1,324 @@
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use reqwest::Url;
+use reqwest::cookie::CookieStore;
+use reqwest::header::HeaderValue;
+
+/// Netscape cookies.txt 中以此为前缀的行表示 HttpOnly cookie
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// 一个 cookie，字段与 Netscape cookies.txt 的各列对应
+#[derive(Debug, Clone, PartialEq)]
+struct Cookie {
+    /// 不带前导 `.` 的域名
+    domain: String,
+    /// 是否同时发送给子域名
+    include_subdomains: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    /// 过期时间（Unix 秒），None 表示会话 cookie
+    expires: Option<u64>,
+    name: String,
+    value: String,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+        let domain_ok = host == self.domain || (self.include_subdomains && host.ends_with(&format!(".{}", self.domain)));
+        domain_ok && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// 请求路径是否在 cookie 的路径之下
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// 没有 Path 属性时使用请求路径所在的目录
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => url.path()[..index].to_string(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// 所有请求共享的 cookie，可以从 Netscape cookies.txt 读取和保存
+///
+/// 通过 `cookie_provider` 交给客户端，HEAD 探测、各区段请求以及重定向中收到的 cookie 都记录在这里。
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// 读取 cookies.txt，过期的 cookie 会被忽略
+    pub fn load(path: &str) -> io::Result<CookieJar> {
+        let content = std::fs::read_to_string(path)?;
+        let now = now();
+        let cookies = content
+            .lines()
+            .filter_map(parse_netscape_line)
+            .filter(|cookie| !cookie.is_expired(now))
+            .collect();
+        Ok(CookieJar { cookies: Mutex::new(cookies) })
+    }
+
+    /// 保存为 cookies.txt；`keep_session_cookies` 为 false 时不保存会话 cookie
+    pub fn save(&self, path: &str, keep_session_cookies: bool) -> io::Result<()> {
+        let now = now();
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "# Netscape HTTP Cookie File")?;
+        writeln!(file, "# 由 wget-rs 生成，请勿手动修改\n")?;
+        for cookie in self.cookies.lock().unwrap().iter() {
+            if cookie.is_expired(now) || (cookie.expires.is_none() && !keep_session_cookies) {
+                continue;
+            }
+            writeln!(
+                file,
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                if cookie.http_only { HTTP_ONLY_PREFIX } else { "" },
+                if cookie.include_subdomains { "." } else { "" },
+                cookie.domain,
+                if cookie.include_subdomains { "TRUE" } else { "FALSE" },
+                cookie.path,
+                if cookie.secure { "TRUE" } else { "FALSE" },
+                cookie.expires.unwrap_or(0),
+                cookie.name,
+                cookie.value
+            )?;
+        }
+        file.flush()
+    }
+
+    /// 加入或替换同一域名、路径和名称的 cookie；已过期的 cookie 表示删除
+    fn store(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|c| !(c.domain == cookie.domain && c.path == cookie.path && c.name == cookie.name));
+        if !cookie.is_expired(now()) {
+            cookies.push(cookie);
+        }
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for header in cookie_headers {
+            if let Some(cookie) = header.to_str().ok().and_then(|value| parse_set_cookie(value, url, now())) {
+                self.store(cookie);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let now = now();
+        let cookies = self.cookies.lock().unwrap();
+        let mut matched: Vec<&Cookie> = cookies.iter().filter(|c| !c.is_expired(now) && c.matches(url)).collect();
+        // 路径更具体的 cookie 排在前面
+        matched.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        if matched.is_empty() {
+            return None;
+        }
+        let header = matched.iter().map(|c| format!("{}={}", c.name, c.value)).collect::<Vec<_>>().join("; ");
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+/// 解析 cookies.txt 中的一行：域名、是否包含子域名、路径、是否仅 HTTPS、过期时间、名称、值
+fn parse_netscape_line(line: &str) -> Option<Cookie> {
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(rest) => (rest, true),
+        None => (line, false),
+    };
+    if line.trim().is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+    let [domain, include_subdomains, path, secure, expires, name, value] = fields[..] else {
+        return None;
+    };
+    let expires: u64 = expires.trim().parse().ok()?;
+    Some(Cookie {
+        include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE") || domain.starts_with('.'),
+        domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+        path: path.to_string(),
+        secure: secure.eq_ignore_ascii_case("TRUE"),
+        http_only,
+        expires: (expires != 0).then_some(expires),
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Domain 属性是否可以由多个主机共享：至少有两个标签，且没有空标签
+fn is_shared_domain(domain: &str) -> bool {
+    domain.contains('.') && domain.split('.').all(|label| !label.is_empty())
+}
+
+/// 解析服务器返回的 `Set-Cookie`；Domain 与请求的主机不符时拒绝
+fn parse_set_cookie(header: &str, url: &Url, now: u64) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut cookie = Cookie {
+        domain: host.clone(),
+        include_subdomains: false,
+        path: default_path(url),
+        secure: false,
+        http_only: false,
+        expires: None,
+        name: name.to_string(),
+        value: value.trim().to_string(),
+    };
+
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if domain == host {
+                    // 单标签主机（例如 localhost）和 IP 地址只能设置只属于自己的 cookie
+                    cookie.include_subdomains = is_shared_domain(&domain) && url.domain().is_some();
+                    continue;
+                }
+                // `Domain=com` 之类的顶级域名会让 cookie 发给整个顶级域名下的所有网站
+                if !is_shared_domain(&domain) || url.domain().is_none() || !host.ends_with(&format!(".{}", domain)) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.include_subdomains = true;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "expires" => {
+                if let Ok(time) = httpdate::parse_http_date(value) {
+                    cookie.expires = Some(time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+                }
+            }
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            _ => {}
+        }
+    }
+    // Max-Age 优先于 Expires；小于等于 0 表示立即过期
+    if let Some(max_age) = max_age {
+        cookie.expires = Some(if max_age <= 0 { 0 } else { now + max_age as u64 });
+    }
+    Some(cookie)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    fn cookie_header(jar: &CookieJar, target: &str) -> Option<String> {
+        jar.cookies(&url(target)).map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_parse_set_cookie() {
+        let page = url("https://portal.example.com/downloads/list.html");
+        let cookie = parse_set_cookie("sid=abc123; Path=/; Domain=.example.com; Secure; HttpOnly; Max-Age=60", &page, 1000).unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(cookie.include_subdomains && cookie.secure && cookie.http_only);
+        assert_eq!(cookie.path, "/");
+        assert_eq!(cookie.expires, Some(1060));
+
+        let session = parse_set_cookie("lang=zh", &page, 1000).unwrap();
+        assert_eq!(session.domain, "portal.example.com");
+        assert_eq!(session.path, "/downloads");
+        assert_eq!(session.expires, None);
+
+        // 不能为其他域名设置 cookie
+        assert!(parse_set_cookie("sid=x; Domain=other.com", &page, 1000).is_none());
+    }
+
+    #[test]
+    fn test_parse_set_cookie_rejects_top_level_domains() {
+        let page = url("https://portal.example.com/");
+        assert!(parse_set_cookie("sid=x; Domain=com", &page, 1000).is_none());
+        assert!(parse_set_cookie("sid=x; Domain=.com", &page, 1000).is_none());
+        assert!(parse_set_cookie("sid=x; Domain=xample.com", &page, 1000).is_none());
+
+        // IP 地址不能为上级“域名”设置 cookie
+        let ip = url("http://10.1.2.3/");
+        assert!(parse_set_cookie("sid=x; Domain=1.2.3", &ip, 1000).is_none());
+        assert!(!parse_set_cookie("sid=x; Domain=10.1.2.3", &ip, 1000).unwrap().include_subdomains);
+
+        // 与单标签主机相同的 Domain 只对这个主机有效
+        let local = parse_set_cookie("sid=x; Domain=localhost", &url("http://localhost:8080/"), 1000).unwrap();
+        assert_eq!(local.domain, "localhost");
+        assert!(!local.include_subdomains);
+    }
+
+    #[test]
+    fn test_cookie_matching() {
+        let jar = CookieJar::default();
+        let headers = [
+            HeaderValue::from_static("sid=abc; Path=/; Domain=example.com"),
+            HeaderValue::from_static("token=t1; Path=/files; Secure"),
+        ];
+        jar.set_cookies(&mut headers.iter(), &url("https://example.com/login"));
+
+        assert_eq!(cookie_header(&jar, "https://example.com/files/a.zip").as_deref(), Some("token=t1; sid=abc"));
+        assert_eq!(cookie_header(&jar, "https://cdn.example.com/files/a.zip").as_deref(), Some("sid=abc"));
+        assert_eq!(cookie_header(&jar, "http://example.com/files/a.zip").as_deref(), Some("sid=abc"));
+        assert_eq!(cookie_header(&jar, "https://example.com/filesystem").as_deref(), Some("sid=abc"));
+        assert_eq!(cookie_header(&jar, "https://other.com/"), None);
+
+        // Max-Age=0 删除 cookie
+        let delete = [HeaderValue::from_static("sid=; Path=/; Domain=example.com; Max-Age=0")];
+        jar.set_cookies(&mut delete.iter(), &url("https://example.com/logout"));
+        assert_eq!(cookie_header(&jar, "https://cdn.example.com/"), None);
+    }
+
+    #[test]
+    fn test_netscape_round_trip() {
+        let path = "/tmp/test_wget_rs_cookies.txt";
+        std::fs::write(
+            path,
+            "# Netscape HTTP Cookie File\n\
+             .example.com\tTRUE\t/\tFALSE\t4102444800\tsid\tabc\n\
+             #HttpOnly_portal.example.com\tFALSE\t/dl\tTRUE\t0\tsession\txyz\n\
+             example.com\tFALSE\t/\tFALSE\t1\texpired\told\n",
+        ).unwrap();
+
+        let jar = CookieJar::load(path).unwrap();
+        assert_eq!(cookie_header(&jar, "https://portal.example.com/dl/a.zip").as_deref(), Some("session=xyz; sid=abc"));
+
+        jar.save(path, false).unwrap();
+        let saved = std::fs::read_to_string(path).unwrap();
+        assert!(saved.contains(".example.com\tTRUE\t/\tFALSE\t4102444800\tsid\tabc"));
+        assert!(!saved.contains("session"));
+
+        jar.save(path, true).unwrap();
+        let saved = std::fs::read_to_string(path).unwrap();
+        assert!(saved.contains("#HttpOnly_portal.example.com\tFALSE\t/dl\tTRUE\t0\tsession\txyz"));
+        assert!(!saved.contains("expired"));
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -1,12 +1,354 @@
-use std::fs::{File, OpenOptions};
-use std::io::{Write, Read};
-use std::thread;
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{Read, SeekFrom};
 use std::sync::{Arc, Mutex};
-use reqwest::blocking::Client;
-use reqwest::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, RANGE, ACCEPT_RANGES, HeaderMap};
+use reqwest::{Client, Method};
+use reqwest::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE, ACCEPT_RANGES, ETAG, IF_MODIFIED_SINCE, IF_RANGE, LAST_MODIFIED, HeaderMap, HeaderName};
 use regex::Regex;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::time::{Duration, Instant, SystemTime};
+use std::path::Path;
+use crate::control::{if_range_validator, plan_segments, ControlFile, Segment};
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+use crate::ratelimit::RateLimiter;
+use crate::hash::verify_and_display;
+use crate::cli::FileAllocation;
+use crate::filter::UrlFilter;
+use crate::paths::PathLayout;
+use crate::auth::{send, Authenticator};
+use crate::cookies::CookieJar;
+use crate::proxy::ProxyConfig;
+use crate::console::{info, is_quiet};
+use fs2::FileExt;
+use tokio::fs::File;
+use tokio::task::JoinSet;
+use tokio::io::{AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// 多线程下载时保存控制文件的间隔
+const CONTROL_SAVE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 分段下载时每次写入的最大字节数，远小于区段拆分的下限，保证写入不会越过拆分点
+const WRITE_PIECE_SIZE: usize = 16 * 1024;
+
+/// 单个文件的下载参数
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub output: Option<String>,
+    pub threads: u32,
+    pub continue_download: bool,
+    pub retry: RetryPolicy,
+    /// 空闲线程拆分慢速区段时，每一半至少保留的字节数
+    pub min_split_size: u64,
+    /// 续传时远程文件已改变则报错，而不是重新下载
+    pub strict_resume: bool,
+    /// 下载完成后、重命名为最终文件名前校验的哈希值
+    pub verify_hash: Option<String>,
+    /// 分段下载时输出文件的预分配方式
+    pub file_allocation: FileAllocation,
+    /// 所有下载任务共享的限速器
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// 批量下载时所有文件共用的进度条区域，避免多个进度条互相覆盖
+    pub progress: Option<MultiProgress>,
+    /// 本地文件不比远程文件旧时跳过下载
+    pub timestamping: bool,
+    /// 发送请求前检查 URL（包括重定向目标）的过滤规则
+    pub filter: UrlFilter,
+    /// 没有用 `output` 指定文件名时，文件保存的位置
+    pub layout: PathLayout,
+    /// 目标文件已存在时的处理方式
+    pub existing_file: ExistingFile,
+    /// 替换默认 User-Agent
+    pub user_agent: Option<String>,
+    /// 附加到每个请求的请求头（--header、--referer）
+    pub headers: HeaderMap,
+    /// 自定义的请求方法和请求体，None 时先发送 HEAD 再用 GET 下载
+    pub request: Option<RequestSpec>,
+    /// 所有请求共享的 cookie（--load-cookies、--save-cookies）
+    pub cookies: Option<Arc<CookieJar>>,
+    /// HTTP Basic/Digest 认证（--user、--password、URL 中的用户名密码、~/.netrc）
+    pub auth: Option<Arc<Authenticator>>,
+    /// 代理设置，默认直接连接
+    pub proxy: ProxyConfig,
+    /// 所有任务共享的文件名预留表
+    pub reserved_names: Arc<NameReservations>,
+}
+
+/// `--method`、`--post-data` 等指定的请求
+///
+/// 这类请求不一定可以安全地重复发送，所以只发送一次，不做 HEAD 探测、分段下载、重试或续传，
+/// 响应体按顺序写入文件。
+#[derive(Debug, Clone)]
+pub struct RequestSpec {
+    pub method: Method,
+    pub body: Option<Vec<u8>>,
+}
+
+impl RequestSpec {
+    fn build(&self, client: &Client, url: &str, options: &DownloadOptions) -> reqwest::RequestBuilder {
+        let mut request = client.request(self.method.clone(), url);
+        if let Some(body) = &self.body {
+            // 与 wget 一致，没有用 --header 指定时按表单数据发送
+            if !options.headers.contains_key(CONTENT_TYPE) {
+                request = request.header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+            }
+            request = request.body(body.clone());
+        }
+        request
+    }
+}
+
+/// 目标文件已存在时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExistingFile {
+    /// 保存为 `文件名.1`、`文件名.2`……；用 -O 指定文件名、递归下载、续传或时间戳模式时直接覆盖
+    #[default]
+    Number,
+    /// 跳过已存在的文件（-nc）
+    NoClobber,
+    /// 覆盖前把旧文件依次轮换为 `文件名.1` 到 `文件名.N`（--backups）
+    Backups(u32),
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            output: None,
+            threads: 32,
+            continue_download: false,
+            retry: RetryPolicy::default(),
+            min_split_size: 1024 * 1024,
+            strict_resume: false,
+            verify_hash: None,
+            file_allocation: FileAllocation::Falloc,
+            rate_limiter: None,
+            progress: None,
+            timestamping: false,
+            filter: UrlFilter::default(),
+            layout: PathLayout::default(),
+            existing_file: ExistingFile::default(),
+            user_agent: None,
+            headers: HeaderMap::new(),
+            request: None,
+            cookies: None,
+            auth: None,
+            proxy: ProxyConfig::default(),
+            reserved_names: Arc::default(),
+        }
+    }
+}
+
+/// 下载完成的资源
+#[derive(Debug, Clone)]
+pub struct Downloaded {
+    /// 保存的文件名
+    pub filename: String,
+    /// 跟随重定向后的最终 URL，页面中的相对链接以它为基准
+    pub final_url: String,
+    pub content_type: Option<String>,
+}
+
+impl Downloaded {
+    /// 是否为需要解析链接的 HTML 页面；服务器未给出类型时按扩展名判断
+    pub fn is_html(&self) -> bool {
+        match &self.content_type {
+            Some(content_type) => {
+                let mime = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+                mime == "text/html" || mime == "application/xhtml+xml"
+            }
+            None => {
+                let lower = self.filename.to_ascii_lowercase();
+                lower.ends_with(".html") || lower.ends_with(".htm")
+            }
+        }
+    }
+
+    /// 是否为样式表，`-p` 时需要继续下载其中 `url()` 引用的资源
+    pub fn is_css(&self) -> bool {
+        match &self.content_type {
+            Some(content_type) => content_type.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case("text/css"),
+            None => self.filename.to_ascii_lowercase().ends_with(".css"),
+        }
+    }
+}
+
+/// 服务器返回的错误状态码，用于区分可以重试的错误
+#[derive(Debug)]
+struct HttpError {
+    status: reqwest::StatusCode,
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP error: {} - {}", self.status.as_u16(), self.status.canonical_reason().unwrap_or("Unknown"))
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// 服务器没有按请求的范围返回 206 Partial Content，无法分段下载
+#[derive(Debug)]
+struct RangeNotHonored {
+    reason: String,
+}
+
+impl std::fmt::Display for RangeNotHonored {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "服务器未正确响应 Range 请求: {}", self.reason)
+    }
+}
+
+impl std::error::Error for RangeNotHonored {}
+
+/// 网络错误、连接中断和服务器 5xx/408/429 值得重试，其余 HTTP 错误直接放弃
+fn is_retryable(error: &(dyn std::error::Error + 'static)) -> bool {
+    if error.is::<RangeNotHonored>() {
+        return false;
+    }
+    match error.downcast_ref::<HttpError>() {
+        Some(http_error) => is_retryable_status(http_error.status),
+        None => true,
+    }
+}
+
+/// 服务器临时性的错误（5xx、408、429），下载和 `--spider` 检查都按这个规则重试
+pub(crate) fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429
+}
+
+/// 下载过程中使用的临时文件名
+fn part_path_for(filename: &str) -> String {
+    format!("{}.part", filename)
+}
+
+/// 第一个既不存在、也没有被其他任务预留的 `文件名.N`
+fn numbered_path(filename: &str, reserved: &HashSet<String>) -> String {
+    (1..)
+        .map(|n| format!("{}.{}", filename, n))
+        .find(|candidate| !reserved.contains(candidate) && !Path::new(candidate).exists())
+        .unwrap()
+}
+
+/// 同一次运行中各下载任务正在写入的文件名
+///
+/// 批量下载时不同 URL 可能得到相同的文件名（例如 `/a/big.bin` 和 `/b/big.bin`），
+/// 写入前先在这里预留，避免两个任务同时写同一个 `.part` 文件。
+#[derive(Debug, Default)]
+pub struct NameReservations {
+    names: Mutex<HashSet<String>>,
+}
+
+impl NameReservations {
+    /// 预留 `filename`；`number` 为 true 或文件名已被其他任务预留时，改为预留编号的文件名
+    fn reserve(self: &Arc<Self>, filename: &str, number: bool) -> Reservation {
+        let mut names = self.names.lock().unwrap();
+        let name = if number || names.contains(filename) {
+            numbered_path(filename, &names)
+        } else {
+            filename.to_string()
+        };
+        names.insert(name.clone());
+        Reservation { owner: self.clone(), name }
+    }
+}
+
+/// 预留的文件名，下载结束后释放
+struct Reservation {
+    owner: Arc<NameReservations>,
+    name: String,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.owner.names.lock().unwrap().remove(&self.name);
+    }
+}
+
+/// 把 `文件名.1`……`文件名.N-1` 依次后移一位，再把当前文件改名为 `文件名.1`，最旧的 `文件名.N` 被覆盖
+fn rotate_backups(filename: &str, backups: u32) -> std::io::Result<()> {
+    for n in (1..backups).rev() {
+        let older = format!("{}.{}", filename, n);
+        if Path::new(&older).exists() {
+            std::fs::rename(&older, format!("{}.{}", filename, n + 1))?;
+        }
+    }
+    std::fs::rename(filename, format!("{}.1", filename))
+}
+
+/// 检查目标文件所在目录的文件系统是否还有足够的剩余空间
+fn check_free_space(filename: &str, needed: u64) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let dir = Path::new(filename)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    match fs2::available_space(dir) {
+        Ok(available) if available < needed => Err(format!(
+            "磁盘空间不足: 需要 {} 字节，{} 所在分区仅剩 {} 字节",
+            needed, dir.display(), available
+        ).into()),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            info!("无法获取 {} 的剩余空间: {}，跳过检查", dir.display(), e);
+            Ok(())
+        }
+    }
+}
+
+/// 按指定方式为分段下载的输出文件预分配空间；`truncate` 为 false 时保留已下载的内容
+fn preallocate(filename: &str, size: u64, mode: FileAllocation, truncate: bool) -> std::io::Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(truncate)
+        .open(filename)?;
+
+    match mode {
+        FileAllocation::None => Ok(()),
+        FileAllocation::Trunc => file.set_len(size),
+        // 文件系统不支持 fallocate 时退回到 set_len
+        FileAllocation::Falloc => file.allocate(size).or_else(|_| file.set_len(size)),
+    }
+}
+
+/// 批量下载时把进度条加入共享的显示区域
+fn track_progress(pb: ProgressBar, options: &DownloadOptions) -> ProgressBar {
+    if is_quiet() {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+        return pb;
+    }
+    match &options.progress {
+        Some(multi) => multi.add(pb),
+        None => pb,
+    }
+}
+
+fn get_modified_time(filename: &str) -> Option<SystemTime> {
+    std::fs::metadata(filename)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// 本地文件不早于远程的 Last-Modified 且大小一致（远程大小未知时不比较）时，认为无需重新下载
+fn is_up_to_date(filename: &str, headers: &HeaderMap) -> bool {
+    let Some(local_time) = get_modified_time(filename) else {
+        return false;
+    };
+    let Some(remote_time) = header_value(headers, LAST_MODIFIED).and_then(|v| httpdate::parse_http_date(&v).ok()) else {
+        return false;
+    };
+    let remote_size = header_value(headers, CONTENT_LENGTH).and_then(|v| v.parse::<u64>().ok());
+    local_time >= remote_time && remote_size.is_none_or(|size| get_file_size(filename) == Some(size))
+}
+
+/// 把文件的修改时间设为服务器给出的 Last-Modified，下次可据此判断是否需要更新
+fn apply_remote_timestamp(filename: &str, headers: &HeaderMap) {
+    if let Some(remote_time) = header_value(headers, LAST_MODIFIED).and_then(|v| httpdate::parse_http_date(&v).ok())
+        && let Err(e) = std::fs::File::options().write(true).open(filename).and_then(|f| f.set_modified(remote_time))
+    {
+        info!("无法设置 {} 的修改时间: {}", filename, e);
+    }
+}
 
 fn get_file_size(filename: &str) -> Option<u64> {
     std::fs::metadata(filename)
@@ -14,12 +356,12 @@ fn get_file_size(filename: &str) -> Option<u64> {
         .map(|metadata| metadata.len())
 }
 
-fn check_resume_capability(client: &Client, url: &str, start_pos: u64) -> Result<(bool, u64), Box<dyn std::error::Error>> {
+async fn check_resume_capability(client: &Client, url: &str, start_pos: u64, auth: Option<&Authenticator>) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
     let range_header = format!("bytes={}-", start_pos);
-    let response = client
+    let request = client
         .get(url)
-        .header(RANGE, range_header)
-        .send()?;
+        .header(RANGE, range_header);
+    let response = send(request, auth).await?;
     
     let status = response.status();
     if status.as_u16() == 206 {
@@ -47,26 +389,60 @@ fn check_resume_capability(client: &Client, url: &str, start_pos: u64) -> Result
     }
 }
 
-fn validate_response(response: &reqwest::blocking::Response, _expected_filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn validate_response(response: &reqwest::Response, _expected_filename: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let status = response.status();
     
     // 只检查 HTTP 状态码
     if !status.is_success() {
-        return Err(format!("HTTP error: {} - {}", status.as_u16(), status.canonical_reason().unwrap_or("Unknown")).into());
+        return Err(Box::new(HttpError { status }));
     }
     // 不再对内容类型做强制检查
     
     Ok(())
 }
 
-fn create_client() -> Result<Client, Box<dyn std::error::Error>> {
-    let pkg_version = option_env!("CARGO_PKG_VERSION").unwrap_or("0.1.0");
-    let user_agent = format!("Wget/{} ({})", pkg_version, std::env::consts::OS);
-    Client::builder()
-        .user_agent(user_agent)
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .build()
-        .map_err(|e| e.into())
+/// 请求中使用的 User-Agent，robots.txt 也按它匹配规则；默认为 `Wget/版本 (系统)`
+pub fn user_agent(options: &DownloadOptions) -> String {
+    options.user_agent.clone().unwrap_or_else(|| {
+        let pkg_version = option_env!("CARGO_PKG_VERSION").unwrap_or("0.1.0");
+        format!("Wget/{} ({})", pkg_version, std::env::consts::OS)
+    })
+}
+
+pub fn create_client(options: &DownloadOptions) -> Result<Client, Box<dyn std::error::Error + Send + Sync>> {
+
+    // 重定向目标同样要经过过滤规则检查，最多跟随 10 次；需要认证时记录每次重定向，以便 401 后重新发送
+    let filter = options.filter.clone();
+    let auth = options.auth.clone();
+    let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 10 {
+            return attempt.error("重定向次数过多");
+        }
+        match filter.check(attempt.url()) {
+            Ok(()) => {
+                if let (Some(auth), Some(from)) = (&auth, attempt.previous().last()) {
+                    auth.record_redirect(from, attempt.url(), attempt.status());
+                }
+                attempt.follow()
+            }
+            Err(reason) => {
+                let message = format!("重定向到 {} 被过滤规则排除: {}", attempt.url(), reason);
+                attempt.error(message)
+            }
+        }
+    });
+
+    // 自定义请求头作为默认请求头，HEAD 探测、续传检查和每个区段的请求都会带上
+    let mut builder = Client::builder()
+        .user_agent(user_agent(options))
+        .default_headers(options.headers.clone())
+        .redirect(redirect);
+    if let Some(jar) = &options.cookies {
+        builder = builder.cookie_provider(jar.clone());
+    }
+    // 所有客户端都从这里创建，HEAD 探测、各区段的请求和 robots.txt 都经过同样的代理
+    builder = options.proxy.apply(builder)?;
+    builder.build().map_err(|e| e.into())
 }
 
 fn extract_filename_from_headers(headers: &HeaderMap) -> Option<String> {
@@ -82,12 +458,76 @@ fn extract_filename_from_headers(headers: &HeaderMap) -> Option<String> {
 
 fn extract_filename_from_url(url: &str) -> String {
     url.split('/')
-        .last()
+        .next_back()
         .filter(|s| !s.is_empty())
         .unwrap_or("output")
         .to_string()
 }
 
+fn header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers.get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// 根据本地文件的内容判断类型，用于没有重新下载的文件（未修改或 `-nc` 时已存在）
+///
+/// 这时没有响应头，递归下载仍然需要知道 `.php`、没有扩展名等页面是否为 HTML 才能继续跟随其中的链接。
+fn local_content_type(filename: &str) -> Option<String> {
+    let mut head = Vec::new();
+    std::fs::File::open(filename).ok()?.take(1024).read_to_end(&mut head).ok()?;
+    let text = String::from_utf8_lossy(&head).trim_start_matches(['\u{feff}', ' ', '\t', '\r', '\n']).to_ascii_lowercase();
+    if ["<!doctype html", "<html", "<head", "<body"].iter().any(|tag| text.starts_with(tag)) {
+        Some("text/html".to_string())
+    } else if filename.to_ascii_lowercase().ends_with(".css") {
+        Some("text/css".to_string())
+    } else {
+        None
+    }
+}
+
+/// 解析 `Content-Range: bytes start-end/total`，total 未知（`*`）时为 None
+fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    let (span, total) = range.split_once('/')?;
+    let (start, end) = span.split_once('-')?;
+    let start = start.trim().parse().ok()?;
+    let end = end.trim().parse().ok()?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    if end < start {
+        return None;
+    }
+    Some((start, end, total))
+}
+
+/// 检查分段请求的响应是否为 206，且 Content-Range 与请求的范围一致
+fn check_partial_response(
+    response: &reqwest::Response,
+    start: u64,
+    end: u64,
+    total_size: u64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let status = response.status();
+    if status.as_u16() == 200 {
+        return Err(Box::new(RangeNotHonored { reason: "返回了 200 和完整内容".to_string() }));
+    }
+    if status.as_u16() != 206 {
+        return Err(Box::new(HttpError { status }));
+    }
+
+    let content_range = header_value(response.headers(), CONTENT_RANGE)
+        .ok_or_else(|| RangeNotHonored { reason: "206 响应缺少 Content-Range".to_string() })?;
+    match parse_content_range(&content_range) {
+        Some((s, e, total)) if s == start && e == end && total.is_none_or(|t| t == total_size) => Ok(()),
+        _ => Err(Box::new(RangeNotHonored {
+            reason: format!("请求 bytes={}-{}/{}，实际为 {}", start, end, total_size, content_range),
+        })),
+    }
+}
+
 fn supports_range_requests(headers: &HeaderMap) -> bool {
     headers.get(ACCEPT_RANGES)
         .and_then(|v| v.to_str().ok())
@@ -95,75 +535,309 @@ fn supports_range_requests(headers: &HeaderMap) -> bool {
         .unwrap_or(false)
 }
 
-fn download_chunk(
+/// 打开输出文件并定位到指定偏移，供各下载任务并行写入各自的区段
+async fn open_segment_writer(filename: &str, offset: u64) -> std::io::Result<File> {
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(filename).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    Ok(file)
+}
+
+/// 分段下载任务共享的信息
+struct SegmentJob {
+    client: Client,
+    url: String,
+    filename: String,
+    scheduler: Arc<Mutex<Scheduler>>,
+    progress: Arc<Mutex<ProgressBar>>,
+    retry: RetryPolicy,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    auth: Option<Arc<Authenticator>>,
+}
+
+async fn download_chunk(job: &SegmentJob, index: usize) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 从区段已完成的位置开始请求，续传时跳过已写入的字节
+    let (start, end, total_size, validator) = {
+        let scheduler = job.scheduler.lock().unwrap();
+        let segment = scheduler.segment(index);
+        let validator = scheduler.control().if_range().map(|v| v.to_string());
+        (segment.position(), segment.end, scheduler.control().total_size, validator)
+    };
+
+    let range_header = format!("bytes={}-{}", start, end);
+    let mut request = job.client
+        .get(&job.url)
+        .header(RANGE, range_header);
+    // 远程文件在下载过程中改变时，服务器会返回 200 而不是 206
+    if let Some(validator) = validator {
+        request = request.header(IF_RANGE, validator);
+    }
+    let mut response = send(request, job.auth.as_deref()).await?;
+
+    check_partial_response(&response, start, end, total_size)?;
+
+    // 边接收边写入文件中对应的位置，内存占用与文件大小无关
+    let mut dest = open_segment_writer(&job.filename, start).await?;
+    let mut position = start;
+
+    'receive: while let Some(chunk) = response.chunk().await? {
+        if let Some(limiter) = &job.rate_limiter {
+            limiter.acquire(chunk.len() as u64).await;
+        }
+
+        for piece in chunk.chunks(WRITE_PIECE_SIZE) {
+            // 区段的后半部分可能已被空闲任务拆走，每次写入前重新读取结束位置
+            let (end, aborted) = {
+                let scheduler = job.scheduler.lock().unwrap();
+                (scheduler.segment(index).end, scheduler.is_aborted())
+            };
+            if aborted {
+                return Err("下载已取消".into());
+            }
+            if position > end {
+                break 'receive;
+            }
+
+            let n = (piece.len() as u64).min(end - position + 1) as usize;
+            dest.write_all(&piece[..n]).await?;
+            dest.flush().await?;
+            position += n as u64;
+
+            // 只有写入成功的字节才记入控制文件
+            job.scheduler.lock().unwrap().record(index, n as u64);
+            job.progress.lock().unwrap().inc(n as u64);
+        }
+    }
+
+    let segment = job.scheduler.lock().unwrap().segment(index).clone();
+    if !segment.is_complete() {
+        return Err(format!(
+            "区段 {}-{} 提前结束，还差 {} 字节",
+            segment.start, segment.end, segment.len() - segment.downloaded
+        ).into());
+    }
+
+    Ok(())
+}
+
+/// 下载一个区段，失败后从已写入的位置重新请求，直到用完重试次数
+async fn download_chunk_with_retry(job: &SegmentJob, index: usize) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let retry = &job.retry;
+    let mut attempt = 1;
+    loop {
+        let before = job.scheduler.lock().unwrap().segment(index).downloaded;
+        let error = match download_chunk(job, index).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        // 本次尝试有进展（例如连接中途被重置）时重新计算重试次数
+        let segment = job.scheduler.lock().unwrap().segment(index).clone();
+        if segment.downloaded > before {
+            attempt = 1;
+        }
+
+        if !is_retryable(error.as_ref()) || !retry.should_retry(attempt) || job.scheduler.lock().unwrap().is_aborted() {
+            return Err(error);
+        }
+
+        let delay = retry.delay(attempt);
+        job.progress.lock().unwrap().suspend(|| {
+            info!(
+                "区段 {}-{} 下载出错: {}，{} 秒后从 {} 字节处重试 ({}/{})",
+                segment.start, segment.end, error, delay.as_secs_f32(), segment.position(), attempt, retry.tries - 1
+            )
+        });
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// 下载任务：不断领取区段直到没有剩余工作，遇到无法恢复的错误时退出
+async fn segment_worker(job: &SegmentJob) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        let next = job.scheduler.lock().unwrap().next_segment();
+        let Some(index) = next else {
+            return Ok(());
+        };
+
+        let result = download_chunk_with_retry(job, index).await;
+        let mut scheduler = job.scheduler.lock().unwrap();
+        scheduler.release(index, result.is_err());
+        if let Err(e) = result {
+            // 服务器不支持分段时其余任务也无法成功，通知它们尽快停止
+            if e.is::<RangeNotHonored>() {
+                scheduler.abort();
+            }
+            return Err(e);
+        }
+    }
+}
+
+async fn download_multi_threaded(
     client: &Client,
     url: &str,
-    start: u64,
-    end: u64,
-    chunk_data: Arc<Mutex<Vec<u8>>>,
-    progress: Arc<Mutex<ProgressBar>>,
+    filename: &str,
+    control: ControlFile,
+    pb: Arc<Mutex<ProgressBar>>,
+    options: &DownloadOptions,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let chunk_size = end - start + 1;
-    const MAX_CHUNK_SIZE: u64 = 100 * 1024 * 1024; // 100MB limit per chunk
-    
-    if chunk_size > MAX_CHUNK_SIZE {
-        return Err(format!("Chunk size {} exceeds maximum allowed size", chunk_size).into());
+    let control_path = ControlFile::path_for(filename);
+    control.save(&control_path)?;
+
+    let scheduler = Arc::new(Mutex::new(Scheduler::new(control, options.min_split_size)));
+    let job = Arc::new(SegmentJob {
+        client: client.clone(),
+        url: url.to_string(),
+        filename: filename.to_string(),
+        scheduler: scheduler.clone(),
+        progress: pb.clone(),
+        retry: options.retry.clone(),
+        rate_limiter: options.rate_limiter.clone(),
+        auth: options.auth.clone(),
+    });
+    let mut workers = JoinSet::new();
+
+    // 每个连接对应一个异步任务，而不是一个系统线程
+    for _ in 0..options.threads {
+        let job_clone = job.clone();
+        workers.spawn(async move { segment_worker(&job_clone).await });
     }
 
-    let range_header = format!("bytes={}-{}", start, end);
-    let response = client
-        .get(url)
-        .header(RANGE, range_header)
-        .send()?;
+    // 等待所有任务完成，期间定期保存控制文件，进程被中断后可用 -c 继续
+    let mut save_tick = tokio::time::interval(CONTROL_SAVE_INTERVAL);
+    save_tick.tick().await;
+    let mut outcomes = Vec::new();
+    loop {
+        tokio::select! {
+            joined = workers.join_next() => match joined {
+                Some(outcome) => outcomes.push(outcome),
+                None => break,
+            },
+            _ = save_tick.tick() => {
+                let snapshot = scheduler.lock().unwrap().control().clone();
+                snapshot.save(&control_path)?;
+            }
+        }
+    }
 
-    if !response.status().is_success() {
-        return Err(format!("HTTP error: {} - {}", response.status().as_u16(), response.status().canonical_reason().unwrap_or("Unknown")).into());
+    let mut failure: Option<String> = None;
+    let mut range_error = None;
+    for outcome in outcomes {
+        match outcome {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if e.is::<RangeNotHonored>() => {
+                range_error = Some(e);
+            }
+            Ok(Err(e)) => {
+                failure.get_or_insert(format!("下载块失败: {}", e));
+            }
+            Err(_) => {
+                failure.get_or_insert("下载任务 panic".to_string());
+            }
+        }
     }
 
-    let mut buffer = Vec::new();
-    let mut response_reader = response;
-    response_reader.read_to_end(&mut buffer)?;
+    // 服务器实际上不支持分段下载，放弃已写入的区段，改为单线程从头下载
+    if let Some(e) = range_error {
+        ControlFile::remove(&control_path);
+        {
+            let pb_guard = pb.lock().unwrap();
+            pb_guard.finish_and_clear();
+        }
+        let (total_size, downloaded) = {
+            let scheduler = scheduler.lock().unwrap();
+            (scheduler.control().total_size, scheduler.control().downloaded())
+        };
+        if options.strict_resume && downloaded > 0 {
+            return Err(format!("{}，无法续传", e).into());
+        }
+        info!("{}，改用单线程下载...", e);
+        return download_single_with_retry(client, url, filename, total_size, None, false, options).await;
+    }
 
-    {
-        let mut data = chunk_data.lock().unwrap();
-        *data = buffer;
+    if let Some(message) = failure {
+        // 保留已完成的进度，下次可以继续
+        let snapshot = scheduler.lock().unwrap().control().clone();
+        snapshot.save(&control_path)?;
+        return Err(message.into());
     }
 
+    ControlFile::remove(&control_path);
+
     {
-        let pb = progress.lock().unwrap();
-        pb.inc(end - start + 1);
+        let pb_guard = pb.lock().unwrap();
+        pb_guard.finish_with_message("下载完成!");
     }
-
     Ok(())
 }
 
-fn download_single_threaded(
+async fn download_single_threaded(
     client: &Client,
     url: &str,
     filename: &str,
     total_size: u64,
     resume_from: Option<u64>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let start_pos = resume_from.unwrap_or(0);
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let control_path = ControlFile::path_for(filename);
     let mut request = client.get(url);
     
+    // 续传时带上原始的校验信息，远程文件已改变时服务器会返回完整内容
+    let saved_control = resume_from.and_then(|_| ControlFile::load(&control_path).ok());
     if let Some(pos) = resume_from {
         request = request.header(RANGE, format!("bytes={}-", pos));
+        if let Some(validator) = saved_control.as_ref().and_then(ControlFile::if_range) {
+            request = request.header(IF_RANGE, validator);
+        }
     }
     
-    let response = request.send()?;
+    let mut response = send(request, options.auth.as_deref()).await?;
     
     // Validate the response before proceeding
     validate_response(&response, filename)?;
     
-    let expected_status = if resume_from.is_some() { 206 } else { 200 };
-    if response.status().as_u16() != expected_status {
-        if response.status().as_u16() == 416 && resume_from.is_some() {
-            println!("文件已完整下载");
-            return Ok(());
+    let mut resume_from = resume_from;
+    if let Some(pos) = resume_from {
+        match response.status().as_u16() {
+            206 => {
+                let content_range = header_value(response.headers(), CONTENT_RANGE);
+                let range_start = content_range.as_deref().and_then(parse_content_range).map(|(s, _, _)| s);
+                if range_start != Some(pos) {
+                    return Err(Box::new(RangeNotHonored {
+                        reason: format!("请求从 {} 字节开始，实际 Content-Range 为 {}", pos, content_range.unwrap_or_default()),
+                    }));
+                }
+            }
+            // 服务器返回的是完整内容（远程文件已改变或忽略了 Range），直接从头写入
+            200 => {
+                let reason = if saved_control.is_some() { "远程文件已改变" } else { "服务器忽略了 Range 请求" };
+                if options.strict_resume {
+                    return Err(Box::new(RangeNotHonored { reason: format!("{}，无法续传", reason) }));
+                }
+                info!("{}，从头重新下载", reason);
+                resume_from = None;
+            }
+            status => return Err(format!("Unexpected status code: {}", status).into()),
         }
+    } else if response.status().as_u16() != 200 {
         return Err(format!("Unexpected status code: {}", response.status()).into());
     }
+    let start_pos = resume_from.unwrap_or(0);
+
+    // 大小已知且服务器支持 Range 时记录校验信息和进度，中断后可用 -c 安全续传
+    let mut control = if total_size > 0 && (resume_from.is_some() || supports_range_requests(response.headers())) {
+        let control = ControlFile {
+            url: url.to_string(),
+            total_size,
+            etag: header_value(response.headers(), ETAG),
+            last_modified: header_value(response.headers(), LAST_MODIFIED),
+            segments: vec![Segment { start: 0, end: total_size - 1, downloaded: start_pos }],
+        };
+        control.save(&control_path)?;
+        Some(control)
+    } else {
+        None
+    };
     
     let pb = if total_size > 0 {
         let progress = ProgressBar::new(total_size);
@@ -180,6 +854,7 @@ fn download_single_threaded(
             .unwrap());
         progress
     };
+    let pb = track_progress(pb, options);
     
     // Enable steady tick for better responsiveness
     pb.enable_steady_tick(Duration::from_millis(100));
@@ -189,48 +864,375 @@ fn download_single_threaded(
     }
 
     let mut dest = if resume_from.is_some() {
-        OpenOptions::new().append(true).open(filename)?
+        tokio::fs::OpenOptions::new().append(true).open(filename).await?
     } else {
-        File::create(filename)?
+        File::create(filename).await?
     };
     
-    let mut buffer = [0; 8192];
     let mut downloaded = start_pos;
-    let mut response_reader = response;
+    let mut last_save = Instant::now();
 
-    loop {
-        let n = response_reader.read(&mut buffer)?;
-        if n == 0 {
-            break;
+    while let Some(chunk) = response.chunk().await? {
+        if let Some(limiter) = &options.rate_limiter {
+            limiter.acquire(chunk.len() as u64).await;
         }
-        dest.write_all(&buffer[..n])?;
-        downloaded += n as u64;
+        dest.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
         pb.set_position(downloaded);
+
+        if let Some(control) = control.as_mut()
+            && last_save.elapsed() >= CONTROL_SAVE_INTERVAL
+        {
+            // 确认数据已经写入文件后再记录进度
+            dest.flush().await?;
+            control.segments[0].downloaded = downloaded.min(total_size);
+            control.save(&control_path)?;
+            last_save = Instant::now();
+        }
+    }
+    dest.flush().await?;
+
+    if control.is_some() {
+        ControlFile::remove(&control_path);
     }
 
     pb.finish_with_message("下载完成!");
     Ok(())
 }
 
-pub fn download_file(url: &str, output: &Option<String>, threads: u32, continue_download: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let client = create_client()?;
-    let response = client.head(url).send()?;
+/// 单线程下载失败后重试；服务器支持 Range 时从已下载的位置继续，否则重新下载
+async fn download_single_with_retry(
+    client: &Client,
+    url: &str,
+    filename: &str,
+    total_size: u64,
+    resume_from: Option<u64>,
+    resumable: bool,
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let retry = &options.retry;
+    let mut resume_from = resume_from;
+    let mut attempt = 1;
+    loop {
+        let before = get_file_size(filename).unwrap_or(0);
+        let error = match download_single_threaded(client, url, filename, total_size, resume_from, options).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        let downloaded = get_file_size(filename).unwrap_or(0);
+        if resumable && downloaded > before {
+            attempt = 1;
+        }
+
+        if !is_retryable(error.as_ref()) || !retry.should_retry(attempt) {
+            return Err(error);
+        }
+
+        resume_from = if resumable && downloaded > 0 { Some(downloaded) } else { None };
+        let delay = retry.delay(attempt);
+        info!(
+            "下载出错: {}，{} 秒后重试 ({}/{})",
+            error, delay.as_secs_f32(), attempt, retry.tries - 1
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// 下载文件并返回最终保存的文件名
+///
+/// 下载过程中数据写入 `文件名.part`，传输完成且哈希校验通过后才重命名为最终文件名，
+/// 失败或中断时不会留下看起来完整的文件，也不会覆盖已有的同名文件。
+pub async fn download_file(url: &str, options: &DownloadOptions) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    download_resource(url, options).await.map(|downloaded| downloaded.filename)
+}
+
+/// 与 [`download_file`] 相同，同时返回递归下载解析页面时需要的响应信息
+pub async fn download_resource(url: &str, options: &DownloadOptions) -> Result<Downloaded, Box<dyn std::error::Error + Send + Sync>> {
+    let parsed_url = reqwest::Url::parse(url)?;
+    if let Err(reason) = options.filter.check(&parsed_url) {
+        return Err(format!("{} 被过滤规则排除: {}", url, reason).into());
+    }
+
+    let client = create_client(options)?;
+    let mut request = match &options.request {
+        Some(spec) => spec.build(&client, url, options),
+        None => client.head(url),
+    };
+
+    // 时间戳模式下用条件请求询问服务器本地副本之后文件是否有修改
+    let expected_filename = options.output.clone().unwrap_or_else(|| {
+        options.layout.file_path(&parsed_url, &extract_filename_from_url(url)).to_string_lossy().into_owned()
+    });
+    if options.existing_file == ExistingFile::NoClobber && Path::new(&expected_filename).exists() {
+        info!("{} 已存在，不再下载", expected_filename);
+        let content_type = local_content_type(&expected_filename);
+        return Ok(Downloaded { filename: expected_filename, final_url: url.to_string(), content_type });
+    }
+    if options.timestamping
+        && let Some(local_time) = get_modified_time(&expected_filename)
+    {
+        request = request.header(IF_MODIFIED_SINCE, httpdate::fmt_http_date(local_time));
+    }
+    let response = send(request, options.auth.as_deref()).await?;
 
     let status = response.status();
-    println!("服务器响应状态码: {} {}", status.as_u16(), status.canonical_reason().unwrap_or(""));
+    info!("服务器响应状态码: {} {}", status.as_u16(), status.canonical_reason().unwrap_or(""));
+
+    let final_url = response.url().to_string();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        info!("{} 未修改，跳过下载", expected_filename);
+        let content_type = local_content_type(&expected_filename);
+        return Ok(Downloaded { filename: expected_filename, final_url, content_type });
+    }
 
     if !status.is_success() {
-        return Err(format!("HTTP error: {} - {}", status.as_u16(), status.canonical_reason().unwrap_or("Unknown")).into());
+        return Err(Box::new(HttpError { status }));
     }
 
     let headers = response.headers().clone();
+    // 自定义请求的响应体就是要保存的内容，不再另外发送 GET
+    let mut body = options.request.is_some().then_some(response);
 
-    let filename = match output {
+    if options.output.as_deref() == Some("-") {
+        match body {
+            Some(response) => save_response(response, &mut tokio::io::stdout(), options).await?,
+            None => stream_to_stdout(&client, url, &headers, options).await?,
+        }
+        return Ok(Downloaded { filename: "-".to_string(), final_url, content_type: header_value(&headers, CONTENT_TYPE) });
+    }
+
+    let mut filename = match &options.output {
         Some(name) => name.clone(),
-        None => extract_filename_from_headers(&headers)
-            .or_else(|| Some(extract_filename_from_url(url)))
-            .unwrap(),
+        None => {
+            let name = extract_filename_from_headers(&headers).unwrap_or_else(|| extract_filename_from_url(url));
+            options.layout.file_path(&parsed_url, &name).to_string_lossy().into_owned()
+        }
+    };
+
+    // 服务器给出的文件名可能与按 URL 推断的不同，需要再检查一次
+    let exists = Path::new(&filename).exists();
+    if exists && options.existing_file == ExistingFile::NoClobber {
+        info!("{} 已存在，不再下载", filename);
+        return Ok(Downloaded { filename, final_url, content_type: header_value(&headers, CONTENT_TYPE) });
+    }
+    let number = exists
+        && options.existing_file == ExistingFile::Number
+        && options.output.is_none()
+        && !options.continue_download
+        && !options.timestamping;
+
+    // 并发的任务可能得到同一个文件名，写入前先预留；已被其他任务预留时同样改用编号的文件名
+    let reservation = options.reserved_names.reserve(&filename, number);
+    if reservation.name != filename {
+        if number {
+            info!("{} 已存在，保存为 {}", filename, reservation.name);
+        } else {
+            info!("{} 正在由其他任务下载，保存为 {}", filename, reservation.name);
+        }
+        filename = reservation.name.clone();
+    }
+
+    // 服务器不支持条件请求时，比较 Last-Modified 和文件大小
+    if options.timestamping && is_up_to_date(&filename, &headers) {
+        info!("{} 已是最新，跳过下载", filename);
+        return Ok(Downloaded { filename, final_url, content_type: header_value(&headers, CONTENT_TYPE) });
+    }
+
+    // 自动创建 -P、-x 或递归下载需要的目录
+    if let Some(dir) = Path::new(&filename).parent()
+        && !dir.as_os_str().is_empty()
+    {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| format!("创建目录 {} 失败: {}", dir.display(), e))?;
+    }
+
+    let part_path = part_path_for(&filename);
+
+    // 兼容直接写入最终文件名的旧版本：续传时把已有文件当作未完成的下载。
+    // 复制而不是重命名，续传检查或下载失败时原来的文件保持不变，新文件完整后才替换它
+    if options.continue_download && !Path::new(&part_path).exists() && Path::new(&filename).exists() {
+        info!("将已存在的 {} 复制为未完成的文件继续下载", filename);
+        std::fs::copy(&filename, &part_path)?;
+    }
+
+    match body.take() {
+        Some(response) => save_response(response, &mut File::create(&part_path).await?, options).await?,
+        None => fetch_to_part(&client, url, &headers, &part_path, options).await?,
+    }
+
+    if let Some(expected_hash) = &options.verify_hash {
+        // 计算哈希需要读完整个文件，放到阻塞线程池中执行
+        let (path, name, expected) = (part_path.clone(), filename.clone(), expected_hash.clone());
+        let verified = tokio::task::spawn_blocking(move || {
+            verify_and_display(&path, &name, &expected).map_err(|e| e.to_string())
+        }).await?;
+        if let Err(e) = verified {
+            // 内容已损坏，保留它也无法续传
+            std::fs::remove_file(&part_path).ok();
+            return Err(e.into());
+        }
+    }
+
+    if let ExistingFile::Backups(backups) = options.existing_file
+        && Path::new(&filename).exists()
+    {
+        rotate_backups(&filename, backups)?;
+    }
+    std::fs::rename(&part_path, &filename)?;
+    apply_remote_timestamp(&filename, &headers);
+    info!("文件保存为: {}", filename);
+    Ok(Downloaded {
+        filename,
+        final_url,
+        content_type: header_value(&headers, CONTENT_TYPE),
+    })
+}
+
+/// 把已经收到的响应体按顺序写入 `writer`，用于只发送一次的自定义请求
+async fn save_response(
+    mut response: reqwest::Response,
+    writer: &mut (impl AsyncWrite + Unpin),
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pb = new_progress_bar(response.content_length().unwrap_or(0), options);
+    let mut written = 0;
+    while let Some(chunk) = response.chunk().await? {
+        if let Some(limiter) = &options.rate_limiter {
+            limiter.acquire(chunk.len() as u64).await;
+        }
+        writer.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        pb.set_position(written);
+    }
+    writer.flush().await?;
+    pb.finish_and_clear();
+    Ok(())
+}
+
+/// 单线程传输使用的进度条，大小未知时显示为转圈
+fn new_progress_bar(total_size: u64, options: &DownloadOptions) -> ProgressBar {
+    let pb = if total_size > 0 {
+        let progress = ProgressBar::new(total_size);
+        progress.set_style(ProgressStyle::default_bar()
+            .template("{bar:40.cyan/blue} {bytes}/{total_bytes} {percent}% {eta}")
+            .unwrap()
+            .progress_chars("##-"));
+        progress
+    } else {
+        let progress = ProgressBar::new_spinner();
+        progress.set_style(ProgressStyle::default_spinner()
+            .template("{spinner:.green} {bytes} downloaded... {elapsed}")
+            .unwrap());
+        progress
     };
+    let pb = track_progress(pb, options);
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+/// `-O -`：单线程按顺序把响应体写到标准输出
+///
+/// 已经输出的数据无法撤回，中断后只有服务器支持 Range 时才从已输出的位置继续，
+/// 并用 `If-Range` 确认远程文件没有改变，否则直接报错。
+async fn stream_to_stdout(
+    client: &Client,
+    url: &str,
+    headers: &HeaderMap,
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let total_size: u64 = header_value(headers, CONTENT_LENGTH).and_then(|v| v.parse().ok()).unwrap_or(0);
+    let (etag, last_modified) = (header_value(headers, ETAG), header_value(headers, LAST_MODIFIED));
+    let validator = if_range_validator(etag.as_deref(), last_modified.as_deref());
+    // 没有可用于 If-Range 的校验值时无法确认续传的是同一个版本
+    let resumable = total_size > 0 && supports_range_requests(headers) && validator.is_some();
+    let pb = new_progress_bar(total_size, options);
+
+    let mut stdout = tokio::io::stdout();
+    let mut written = 0;
+    let retry = &options.retry;
+    let mut attempt = 1;
+    loop {
+        let before = written;
+        let error = match stream_once(client, url, validator, &mut stdout, &mut written, &pb, options).await {
+            Ok(()) => break,
+            Err(e) => e,
+        };
+        if written > before {
+            attempt = 1;
+        }
+        if !is_retryable(error.as_ref()) || !retry.should_retry(attempt) || (written > 0 && !resumable) {
+            pb.abandon();
+            return Err(error);
+        }
+        let delay = retry.delay(attempt);
+        info!(
+            "下载出错: {}，{} 秒后从 {} 字节处重试 ({}/{})",
+            error, delay.as_secs_f32(), written, attempt, retry.tries - 1
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+    stdout.flush().await?;
+    pb.finish_and_clear();
+    Ok(())
+}
+
+/// 请求一次并把收到的数据追加到标准输出，`written` 记录已经输出的字节数
+async fn stream_once(
+    client: &Client,
+    url: &str,
+    validator: Option<&str>,
+    stdout: &mut tokio::io::Stdout,
+    written: &mut u64,
+    pb: &ProgressBar,
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = client.get(url);
+    if *written > 0 {
+        request = request.header(RANGE, format!("bytes={}-", written));
+        if let Some(validator) = validator {
+            request = request.header(IF_RANGE, validator);
+        }
+    }
+    let mut response = send(request, options.auth.as_deref()).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Box::new(HttpError { status }));
+    }
+    if *written > 0 {
+        let content_range = header_value(response.headers(), CONTENT_RANGE);
+        let range_start = content_range.as_deref().and_then(parse_content_range).map(|(s, _, _)| s);
+        if status.as_u16() != 206 || range_start != Some(*written) {
+            return Err(Box::new(RangeNotHonored {
+                reason: format!("已输出 {} 字节，远程文件已改变或服务器忽略了 Range 请求", written),
+            }));
+        }
+    }
+
+    while let Some(chunk) = response.chunk().await? {
+        if let Some(limiter) = &options.rate_limiter {
+            limiter.acquire(chunk.len() as u64).await;
+        }
+        stdout.write_all(&chunk).await?;
+        *written += chunk.len() as u64;
+        pb.set_position(*written);
+    }
+    Ok(())
+}
+
+/// 执行实际传输，数据写入临时文件 `filename`
+async fn fetch_to_part(
+    client: &Client,
+    url: &str,
+    headers: &HeaderMap,
+    filename: &str,
+    options: &DownloadOptions,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let threads = options.threads;
+    let mut continue_download = options.continue_download;
 
     let total_size = headers
         .get(CONTENT_LENGTH)
@@ -238,37 +1240,64 @@ pub fn download_file(url: &str, output: &Option<String>, threads: u32, continue_
         .and_then(|len| len.parse().ok())
         .unwrap_or(0);
 
+    let etag = header_value(headers, ETAG);
+    let last_modified = header_value(headers, LAST_MODIFIED);
+
+    // 存在控制文件说明上次是多线程下载，按区段续传
+    let control_path = ControlFile::path_for(filename);
+    let mut resumed_control = None;
+    if continue_download && Path::new(&control_path).exists() {
+        match ControlFile::load(&control_path) {
+            Ok(control) if !control.matches(url, total_size, etag.as_deref(), last_modified.as_deref()) => {
+                if options.strict_resume {
+                    return Err("远程文件已改变（ETag/Last-Modified 或大小不一致），无法续传".into());
+                }
+                info!("远程文件已改变，将重新下载");
+            }
+            Ok(control) if control.fits_file(get_file_size(filename).unwrap_or(0)) => {
+                info!("发现控制文件，已完成 {}/{} 字节，继续分段下载", control.downloaded(), control.total_size);
+                resumed_control = Some(control);
+            }
+            Ok(_) => info!("已下载的文件与控制文件不一致，将重新下载"),
+            Err(e) => info!("读取控制文件失败: {}，将重新下载", e),
+        }
+        if resumed_control.is_none() {
+            ControlFile::remove(&control_path);
+            continue_download = false;
+        }
+    }
+
     // 处理断点续传逻辑
-    let (resume_from, actual_total_size) = if continue_download {
-        if let Some(existing_size) = get_file_size(&filename) {
+    let (resume_from, actual_total_size) = if continue_download && resumed_control.is_none() {
+        if let Some(existing_size) = get_file_size(filename) {
             if existing_size > 0 {
-                println!("发现已存在的文件，大小: {} 字节", existing_size);
+                info!("发现已存在的文件，大小: {} 字节", existing_size);
                 
                 // 检查是否支持断点续传
-                match check_resume_capability(&client, url, existing_size) {
+                match check_resume_capability(client, url, existing_size, options.auth.as_deref()).await {
                     Ok((supports_resume, server_total_size)) => {
                         if supports_resume {
-                            println!("服务器支持断点续传，从 {} 字节处继续下载", existing_size);
+                            info!("服务器支持断点续传，从 {} 字节处继续下载", existing_size);
                             (Some(existing_size), server_total_size)
                         } else if existing_size >= server_total_size {
-                            println!("文件已完整下载");
+                            info!("文件已完整下载");
                             return Ok(());
                         } else {
-                            println!("服务器不支持断点续传，将重新下载文件");
+                            info!("服务器不支持断点续传，将重新下载文件");
                             (None, server_total_size)
                         }
                     }
                     Err(e) => {
-                        println!("检查断点续传支持时出错: {}，将重新下载", e);
+                        info!("检查断点续传支持时出错: {}，将重新下载", e);
                         (None, total_size)
                     }
                 }
             } else {
-                println!("发现空文件，将重新下载");
+                info!("发现空文件，将重新下载");
                 (None, total_size)
             }
         } else {
-            println!("未发现已存在的文件，开始新下载");
+            info!("未发现已存在的文件，开始新下载");
             (None, total_size)
         }
     } else {
@@ -277,11 +1306,21 @@ pub fn download_file(url: &str, output: &Option<String>, threads: u32, continue_
 
     let final_total_size = if actual_total_size > 0 { actual_total_size } else { total_size };
 
+    // 开始传输前确认磁盘空间足够，避免下载了大半才失败
+    if final_total_size > 0 {
+        let existing = if continue_download || resumed_control.is_some() {
+            get_file_size(filename).unwrap_or(0)
+        } else {
+            0
+        };
+        check_free_space(filename, final_total_size.saturating_sub(existing))?;
+    }
+
     // If file size is unknown (0), still show progress bar but with unknown total
     let display_total_size = if final_total_size > 0 { final_total_size } else { 1 };
 
     // Initialize progress bar early and make it visible immediately
-    let pb = Arc::new(Mutex::new(ProgressBar::new(display_total_size)));
+    let pb = Arc::new(Mutex::new(track_progress(ProgressBar::new(display_total_size), options)));
     {
         let pb_guard = pb.lock().unwrap();
         pb_guard.set_style(ProgressStyle::default_bar()
@@ -297,15 +1336,27 @@ pub fn download_file(url: &str, output: &Option<String>, threads: u32, continue_
         }
     }
 
+    if let Some(control) = resumed_control {
+        preallocate(filename, control.total_size, options.file_allocation, false)?;
+        {
+            let pb_guard = pb.lock().unwrap();
+            pb_guard.set_position(control.downloaded());
+        }
+        return download_multi_threaded(client, url, filename, control, pb, options).await;
+    }
+
+    // 单线程下载中断后，只有知道文件大小且服务器支持 Range 时才能从断点继续
+    let resumable = final_total_size > 0 && (supports_range_requests(headers) || resume_from.is_some());
+
     // If file size is unknown or server doesn't support ranges, use single thread
-    if final_total_size == 0 || (!supports_range_requests(&headers) && resume_from.is_none()) || threads == 1 {
+    if final_total_size == 0 || (!supports_range_requests(headers) && resume_from.is_none()) || threads == 1 {
         // Clean up the multi-threaded progress bar before switching to single-threaded
         {
             let pb_guard = pb.lock().unwrap();
             pb_guard.finish_and_clear();
         }
-        println!("使用单线程下载...");
-        return download_single_threaded(&client, url, &filename, final_total_size, resume_from);
+        info!("使用单线程下载...");
+        return download_single_with_retry(client, url, filename, final_total_size, resume_from, resumable, options).await;
     }
 
     // If resuming, use single-threaded download for safety
@@ -315,82 +1366,40 @@ pub fn download_file(url: &str, output: &Option<String>, threads: u32, continue_
             let pb_guard = pb.lock().unwrap();
             pb_guard.finish_and_clear();
         }
-        println!("断点续传模式下使用单线程下载...");
-        return download_single_threaded(&client, url, &filename, final_total_size, resume_from);
+        info!("断点续传模式下使用单线程下载...");
+        return download_single_with_retry(client, url, filename, final_total_size, resume_from, resumable, options).await;
     }
 
-    println!("使用 {} 线程下载，文件大小: {} 字节", threads, final_total_size);
-
-    let chunk_size = final_total_size / threads as u64;
-
-    // If chunk size is too small (less than 1 byte per thread), use single thread  
-    if chunk_size == 0 {
-        // Clean up the multi-threaded progress bar before switching to single-threaded
+    // 每个区段至少 --min-split-size 字节，小文件不值得为每个区段单独发送请求
+    let segments = (final_total_size / options.min_split_size.max(1)).clamp(1, threads.max(1) as u64) as u32;
+    if segments == 1 {
         {
             let pb_guard = pb.lock().unwrap();
             pb_guard.finish_and_clear();
         }
-        println!("文件太小，使用单线程下载...");
-        return download_single_threaded(&client, url, &filename, final_total_size, resume_from);
-    }
-
-    let mut handles = vec![];
-    let mut chunk_data = vec![];
-
-    for i in 0..threads {
-        let start = i as u64 * chunk_size;
-        let end = if i == threads - 1 {
-            final_total_size - 1
-        } else {
-            (i + 1) as u64 * chunk_size - 1
-        };
-
-        let chunk_storage = Arc::new(Mutex::new(Vec::new()));
-        chunk_data.push(chunk_storage.clone());
-
-        let client_clone = client.clone();
-        let url_clone = url.to_string();
-        let pb_clone = pb.clone();
-
-        let handle = thread::spawn(move || {
-            download_chunk(&client_clone, &url_clone, start, end, chunk_storage, pb_clone)
-        });
-
-        handles.push(handle);
+        info!("文件小于 --min-split-size 的两倍，使用单线程下载...");
+        return download_single_with_retry(client, url, filename, final_total_size, resume_from, resumable, options).await;
     }
 
-    // 等待所有线程完成
-    for handle in handles {
-        match handle.join() {
-            Ok(result) => {
-                if let Err(e) = result {
-                    return Err(format!("下载块失败: {}", e).into());
-                }
-            }
-            Err(_) => {
-                return Err("线程 panic".into());
-            }
-        }
-    }
+    info!("使用 {} 线程下载，文件大小: {} 字节", segments, final_total_size);
 
-    // 合并所有块到最终文件
-    let mut dest = File::create(&filename)?;
-    for chunk in chunk_data {
-        let data = chunk.lock().unwrap();
-        dest.write_all(&data)?;
-    }
+    // 预先创建并分配好文件空间，各任务直接写入自己负责的区段
+    preallocate(filename, final_total_size, options.file_allocation, true)?;
 
-    {
-        let pb_guard = pb.lock().unwrap();
-        pb_guard.finish_with_message("下载完成!");
-    }
-    println!("文件保存为: {}", filename);
-    Ok(())
+    let control = ControlFile {
+        url: url.to_string(),
+        total_size: final_total_size,
+        etag,
+        last_modified,
+        segments: plan_segments(final_total_size, segments),
+    };
+    download_multi_threaded(client, url, filename, control, pb, options).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_server::{Route, TestServer};
 
     #[test]
     fn test_supports_range_requests() {
@@ -471,6 +1480,223 @@ mod tests {
         assert_eq!(chunk_size, 0);
     }
 
+    #[tokio::test]
+    async fn test_open_segment_writer() {
+        let temp_path = "/tmp/test_segment_writer.bin";
+        {
+            let file = std::fs::File::create(temp_path).unwrap();
+            file.set_len(8).unwrap();
+        }
+
+        // 乱序写入两个区段，结果应与顺序写入一致
+        for (offset, data) in [(4, b"5678"), (0, b"1234")] {
+            let mut writer = open_segment_writer(temp_path, offset).await.unwrap();
+            writer.write_all(data).await.unwrap();
+            writer.flush().await.unwrap();
+        }
+
+        assert_eq!(std::fs::read(temp_path).unwrap(), b"12345678");
+
+        std::fs::remove_file(temp_path).ok();
+    }
+
+    #[test]
+    fn test_downloaded_is_html() {
+        let page = |filename: &str, content_type: Option<&str>| Downloaded {
+            filename: filename.to_string(),
+            final_url: "https://example.com/".to_string(),
+            content_type: content_type.map(str::to_string),
+        };
+        assert!(page("index.html", Some("text/html; charset=utf-8")).is_html());
+        assert!(page("page", Some("application/xhtml+xml")).is_html());
+        assert!(!page("index.html", Some("application/octet-stream")).is_html());
+        assert!(page("index.htm", None).is_html());
+        assert!(!page("logo.png", None).is_html());
+        assert!(page("style.css", None).is_css());
+        assert!(page("style", Some("text/css")).is_css());
+        assert!(!page("style.css", Some("text/html")).is_css());
+    }
+
+    #[test]
+    fn test_local_content_type() {
+        let dir = "/tmp/test_wget_rs_local_content_type";
+        std::fs::create_dir_all(dir).unwrap();
+        let write = |name: &str, content: &str| {
+            let path = format!("{}/{}", dir, name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        let page = write("index.php", "\u{feff}\n  <!DOCTYPE html><html><body></body></html>");
+        assert_eq!(local_content_type(&page).as_deref(), Some("text/html"));
+        assert_eq!(local_content_type(&write("about", "<HTML><HEAD>")).as_deref(), Some("text/html"));
+        assert_eq!(local_content_type(&write("site.css", "body { color: red }")).as_deref(), Some("text/css"));
+        assert_eq!(local_content_type(&write("data", "plain text")), None);
+        assert_eq!(local_content_type(&format!("{}/missing", dir)), None);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_part_path_for() {
+        assert_eq!(part_path_for("file.zip"), "file.zip.part");
+        assert_eq!(part_path_for("dir/file"), "dir/file.part");
+    }
+
+    #[test]
+    fn test_preallocate() {
+        let temp_path = "/tmp/test_preallocate.bin";
+        std::fs::write(temp_path, b"abc").unwrap();
+
+        // 不截断时保留已有内容并扩展到目标大小
+        preallocate(temp_path, 16, FileAllocation::Trunc, false).unwrap();
+        let content = std::fs::read(temp_path).unwrap();
+        assert_eq!(content.len(), 16);
+        assert_eq!(&content[..3], b"abc");
+
+        preallocate(temp_path, 32, FileAllocation::Falloc, true).unwrap();
+        assert_eq!(get_file_size(temp_path), Some(32));
+
+        preallocate(temp_path, 32, FileAllocation::None, true).unwrap();
+        assert_eq!(get_file_size(temp_path), Some(0));
+
+        std::fs::remove_file(temp_path).ok();
+    }
+
+    #[test]
+    fn test_check_free_space() {
+        assert!(check_free_space("/tmp/test_free_space.bin", 1).is_ok());
+        assert!(check_free_space("/tmp/test_free_space.bin", u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_numbered_path_and_backups() {
+        let dir = Path::new("/tmp/test_existing_file");
+        std::fs::remove_dir_all(dir).ok();
+        std::fs::create_dir_all(dir).unwrap();
+        let file = dir.join("a.txt");
+        let file = file.to_str().unwrap();
+        std::fs::write(file, "v1").unwrap();
+        std::fs::write(format!("{}.1", file), "old").unwrap();
+        assert_eq!(numbered_path(file, &HashSet::new()), format!("{}.2", file));
+
+        // 已存在的文件编号，被其他任务预留的文件名跳过
+        let reservations = Arc::new(NameReservations::default());
+        let first = reservations.reserve(file, true);
+        let second = reservations.reserve(file, true);
+        assert_eq!(first.name, format!("{}.2", file));
+        assert_eq!(second.name, format!("{}.3", file));
+        let other = dir.join("b.txt");
+        let other = other.to_str().unwrap();
+        let claimed = reservations.reserve(other, false);
+        assert_eq!(claimed.name, other);
+        assert_eq!(reservations.reserve(other, false).name, format!("{}.1", other));
+        drop(claimed);
+        assert_eq!(reservations.reserve(other, false).name, other);
+
+        // 只保留 2 个备份：a.txt.1 -> a.txt.2，a.txt -> a.txt.1
+        rotate_backups(file, 2).unwrap();
+        std::fs::write(file, "v2").unwrap();
+        rotate_backups(file, 2).unwrap();
+        assert!(!Path::new(file).exists());
+        assert_eq!(std::fs::read_to_string(format!("{}.1", file)).unwrap(), "v2");
+        assert_eq!(std::fs::read_to_string(format!("{}.2", file)).unwrap(), "v1");
+        assert!(!Path::new(&format!("{}.3", file)).exists());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_multi_threaded_download_returns_promptly() {
+        let body: Vec<u8> = (0..64 * 1024).map(|i| (i % 253) as u8).collect();
+        let server = TestServer::start(vec![("/data.bin", Route::file(body.clone()))]).await;
+        let output = "/tmp/test_wget_rs_prompt.bin";
+        let options = DownloadOptions {
+            output: Some(output.to_string()),
+            threads: 4,
+            min_split_size: 1024,
+            ..DownloadOptions::default()
+        };
+
+        // 任务结束后立即返回，不必等到下一次保存控制文件
+        let started = Instant::now();
+        download_file(&server.url("/data.bin"), &options).await.unwrap();
+        assert!(started.elapsed() < CONTROL_SAVE_INTERVAL, "耗时 {:?}", started.elapsed());
+        assert_eq!(std::fs::read(output).unwrap(), body);
+        std::fs::remove_file(output).ok();
+    }
+
+    #[tokio::test]
+    async fn test_small_file_uses_single_request() {
+        let body = vec![b'x'; 226];
+        let big: Vec<u8> = (0..40 * 1024).map(|i| (i % 251) as u8).collect();
+        let server = TestServer::start(vec![("/page.html", Route::file(body.clone())), ("/big.bin", Route::file(big.clone()))]).await;
+        let output = "/tmp/test_wget_rs_small_file.html";
+        let options = DownloadOptions {
+            output: Some(output.to_string()),
+            min_split_size: 16 * 1024,
+            ..DownloadOptions::default()
+        };
+
+        // 小于 --min-split-size 的文件只发送 HEAD 和一个 GET
+        download_file(&server.url("/page.html"), &options).await.unwrap();
+        assert_eq!(std::fs::read(output).unwrap(), body);
+        let methods: Vec<String> = server.requests().into_iter().map(|r| r.method).collect();
+        assert_eq!(methods, vec!["HEAD", "GET"]);
+
+        // 40k 的文件按 16k 最多分成 2 段，而不是 32 段
+        download_file(&server.url("/big.bin"), &options).await.unwrap();
+        assert_eq!(std::fs::read(output).unwrap(), big);
+        let ranges = server.requests().into_iter().filter(|r| r.path == "/big.bin" && r.range.is_some()).count();
+        assert_eq!(ranges, 2);
+        std::fs::remove_file(output).ok();
+    }
+
+    #[tokio::test]
+    async fn test_continue_keeps_existing_file_on_failure() {
+        let body = vec![b'y'; 4096];
+        let server = TestServer::start(vec![("/done.bin", Route::file(body.clone()))]).await;
+        let output = "/tmp/test_wget_rs_continue_existing.bin";
+        std::fs::write(output, &body).unwrap();
+        std::fs::remove_file(part_path_for(output)).ok();
+        let options = DownloadOptions {
+            output: Some(output.to_string()),
+            continue_download: true,
+            verify_hash: Some("d41d8cd98f00b204e9800998ecf8427e".to_string()),
+            retry: RetryPolicy { tries: 1, ..RetryPolicy::default() },
+            ..DownloadOptions::default()
+        };
+
+        // 校验失败时删除的是 .part，原来完整的文件仍然保留
+        assert!(download_file(&server.url("/done.bin"), &options).await.is_err());
+        assert_eq!(std::fs::read(output).unwrap(), body);
+        assert!(!Path::new(&part_path_for(output)).exists());
+        std::fs::remove_file(output).ok();
+    }
+
+    #[test]
+    fn test_timestamping() {
+        let temp_path = "/tmp/test_timestamping.txt";
+        std::fs::write(temp_path, b"hello").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        headers.insert(CONTENT_LENGTH, "5".parse().unwrap());
+        apply_remote_timestamp(temp_path, &headers);
+        assert_eq!(
+            get_modified_time(temp_path),
+            Some(httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap())
+        );
+        assert!(is_up_to_date(temp_path, &headers));
+
+        // 远程文件更新或大小改变时需要重新下载
+        headers.insert(CONTENT_LENGTH, "6".parse().unwrap());
+        assert!(!is_up_to_date(temp_path, &headers));
+        headers.insert(CONTENT_LENGTH, "5".parse().unwrap());
+        headers.insert(LAST_MODIFIED, "Thu, 22 Oct 2015 07:28:00 GMT".parse().unwrap());
+        assert!(!is_up_to_date(temp_path, &headers));
+
+        std::fs::remove_file(temp_path).ok();
+        assert!(!is_up_to_date(temp_path, &headers));
+    }
+
     #[test]
     fn test_get_file_size() {
         // Test with non-existent file
@@ -499,22 +1725,111 @@ mod tests {
         use crate::cli::{Args};
         
         let args = Args {
-            url: "https://example.com/test.txt".to_string(),
+            urls: vec!["https://example.com/test.txt".to_string()],
+            input_file: None,
+            max_concurrent_downloads: 5,
             output: Some("test.txt".to_string()),
+            quiet: false,
+            directory_prefix: None,
+            force_directories: false,
+            no_host_directories: false,
+            cut_dirs: 0,
+            no_clobber: false,
+            backups: 0,
+            header: vec![],
+            user_agent: None,
+            referer: None,
+            method: None,
+            post_data: None,
+            post_file: None,
+            body_data: None,
+            body_file: None,
+            load_cookies: None,
+            save_cookies: None,
+            keep_session_cookies: false,
+            user: None,
+            password: None,
+            ask_password: false,
+            proxy: None,
+            proxy_user: None,
+            proxy_password: None,
+            no_proxy: false,
             threads: 1,
             continue_: true,
             hash: false,
             verify_hash: None,
+            tries: 5,
+            retry_wait: 1,
+            max_retry_wait: 30,
+            min_split_size: 1024 * 1024,
+            strict_resume: false,
+            file_allocation: FileAllocation::Falloc,
+            limit_rate: None,
+            spider: false,
+            recursive: false,
+            level: 5,
+            no_parent: false,
+            span_hosts: false,
+            timestamping: false,
+            mirror: false,
+            page_requisites: false,
+            convert_links: false,
+            accept: vec![],
+            reject: vec![],
+            accept_regex: None,
+            reject_regex: None,
+            domains: vec![],
+            exclude_domains: vec![],
+            include_directories: vec![],
+            exclude_directories: vec![],
+            execute: vec![],
         };
         
         assert!(args.continue_);
         assert_eq!(args.output, Some("test.txt".to_string()));
     }
 
+    #[test]
+    fn test_is_retryable() {
+        let server_error = HttpError { status: reqwest::StatusCode::SERVICE_UNAVAILABLE };
+        assert!(is_retryable(&server_error));
+
+        let too_many = HttpError { status: reqwest::StatusCode::TOO_MANY_REQUESTS };
+        assert!(is_retryable(&too_many));
+
+        let not_found = HttpError { status: reqwest::StatusCode::NOT_FOUND };
+        assert!(!is_retryable(&not_found));
+
+        let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(is_retryable(&io_error));
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 0-499/1000"), Some((0, 499, Some(1000))));
+        assert_eq!(parse_content_range("bytes 500-999/*"), Some((500, 999, None)));
+        assert_eq!(parse_content_range("bytes */1000"), None);
+        assert_eq!(parse_content_range("bytes 10-5/1000"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+
+    #[test]
+    fn test_range_error_is_not_retryable() {
+        let error = RangeNotHonored { reason: "返回了 200 和完整内容".to_string() };
+        assert!(!is_retryable(&error));
+    }
+
+    #[test]
+    fn test_user_agent() {
+        assert!(user_agent(&DownloadOptions::default()).starts_with("Wget/"));
+        let options = DownloadOptions { user_agent: Some("ArtifactBot/2.0".to_string()), ..DownloadOptions::default() };
+        assert_eq!(user_agent(&options), "ArtifactBot/2.0");
+    }
+
     #[test]
     fn test_create_client() {
         // Test that the client is created successfully with proper user agent
-        let client = create_client();
+        let client = create_client(&DownloadOptions::default());
         assert!(client.is_ok());
         
         // We can't easily test the exact user agent without making a request,
@@ -0,0 +1,260 @@
+use regex::Regex;
+use reqwest::Url;
+
+/// 决定哪些 URL 允许下载的过滤规则，在发送请求之前检查
+///
+/// 主 URL、每个重定向目标以及递归下载中发现的链接都要经过检查。
+#[derive(Debug, Clone, Default)]
+pub struct UrlFilter {
+    /// 允许的文件名：不含通配符时按后缀匹配，否则按通配符匹配整个文件名
+    pub accept: Vec<String>,
+    /// 拒绝的文件名，规则同 `accept`
+    pub reject: Vec<String>,
+    /// 完整 URL 必须匹配的正则表达式
+    pub accept_regex: Option<Regex>,
+    /// 完整 URL 匹配时拒绝的正则表达式
+    pub reject_regex: Option<Regex>,
+    /// 允许的域名（包括其子域名）
+    pub domains: Vec<String>,
+    /// 排除的域名（包括其子域名）
+    pub exclude_domains: Vec<String>,
+    /// 允许的目录（包括其子目录），支持通配符
+    pub include_directories: Vec<String>,
+    /// 排除的目录（包括其子目录），支持通配符
+    pub exclude_directories: Vec<String>,
+}
+
+impl UrlFilter {
+    /// 检查 URL 是否允许下载，不允许时返回原因
+    pub fn check(&self, url: &Url) -> Result<(), String> {
+        self.check_location(url)?;
+        self.check_filename(url)
+    }
+
+    /// 检查域名、目录和正则规则，不检查文件名
+    pub fn check_location(&self, url: &Url) -> Result<(), String> {
+        let host = url.host_str().unwrap_or("");
+        if !self.domains.is_empty() && !self.domains.iter().any(|d| in_domain(host, d)) {
+            return Err(format!("域名 {} 不在 -D 列表中", host));
+        }
+        if let Some(domain) = self.exclude_domains.iter().find(|d| in_domain(host, d)) {
+            return Err(format!("域名 {} 被 --exclude-domains {} 排除", host, domain));
+        }
+
+        let directory = url.path().rsplit_once('/').map_or("", |(dir, _)| dir);
+        if !self.include_directories.is_empty() && !self.include_directories.iter().any(|d| in_directory(directory, d)) {
+            return Err(format!("目录 {}/ 不在 -I 列表中", directory));
+        }
+        if let Some(excluded) = self.exclude_directories.iter().find(|d| in_directory(directory, d)) {
+            return Err(format!("目录 {}/ 被 -X {} 排除", directory, excluded));
+        }
+
+        if let Some(re) = &self.accept_regex
+            && !re.is_match(url.as_str())
+        {
+            return Err(format!("不匹配 --accept-regex {}", re));
+        }
+        if let Some(re) = &self.reject_regex
+            && re.is_match(url.as_str())
+        {
+            return Err(format!("匹配 --reject-regex {}", re));
+        }
+        Ok(())
+    }
+
+    /// 检查 `-A`/`-R` 文件名规则；目录形式的 URL 没有文件名，不受限制
+    pub fn check_filename(&self, url: &Url) -> Result<(), String> {
+        let filename = url.path_segments().and_then(|mut s| s.next_back()).unwrap_or("");
+        if filename.is_empty() {
+            return Ok(());
+        }
+        if !self.accept.is_empty() && !self.accept.iter().any(|p| filename_matches(filename, p)) {
+            return Err(format!("文件名 {} 不在 -A 列表中", filename));
+        }
+        if let Some(pattern) = self.reject.iter().find(|p| filename_matches(filename, p)) {
+            return Err(format!("文件名 {} 被 -R {} 排除", filename, pattern));
+        }
+        Ok(())
+    }
+
+    /// 去掉文件名规则后的副本，递归下载时用于获取需要解析链接的页面
+    pub fn without_filename_rules(&self) -> UrlFilter {
+        UrlFilter {
+            accept: Vec::new(),
+            reject: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
+fn in_domain(host: &str, domain: &str) -> bool {
+    let domain = domain.trim_start_matches('.');
+    host.eq_ignore_ascii_case(domain)
+        || host.to_ascii_lowercase().ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+}
+
+/// `directory` 是否为 `pattern` 指定的目录或其子目录
+fn in_directory(directory: &str, pattern: &str) -> bool {
+    let pattern = format!("/{}", pattern.trim_matches('/'));
+    let pattern = pattern.trim_end_matches('/');
+    if pattern.is_empty() {
+        return true;
+    }
+    // 逐级检查 directory 的每个上级目录，匹配其中任何一级即可
+    let mut prefix = directory;
+    loop {
+        if glob_match(pattern, prefix) {
+            return true;
+        }
+        match prefix.rsplit_once('/') {
+            Some((parent, _)) if !parent.is_empty() => prefix = parent,
+            _ => return false,
+        }
+    }
+}
+
+fn filename_matches(filename: &str, pattern: &str) -> bool {
+    if pattern.contains(['*', '?', '[']) {
+        glob_match(pattern, filename)
+    } else {
+        filename.ends_with(pattern)
+    }
+}
+
+/// 通配符匹配，支持 `*`、`?` 和 `[abc]`/`[a-z]`/`[!abc]`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // 最近一个 `*` 的位置以及它当时对应的文本位置，用于回溯
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match_class(&pattern, p, text[t]),
+            Some(&c) => (c == text[t]).then_some(p + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            }
+            (None, Some((star, matched))) => {
+                p = star + 1;
+                t = matched + 1;
+                backtrack = Some((star, matched + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// 匹配从 `start` 开始的字符类，成功时返回字符类之后的位置
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() && (pattern[i] != ']' || first) {
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            matched |= pattern[i] <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+        first = false;
+    }
+    if i >= pattern.len() {
+        // 没有结束的 `]`，按普通字符处理
+        return (c == '[').then_some(start + 1);
+    }
+    (matched != negated).then_some(i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.pdf", "manual.pdf"));
+        assert!(!glob_match("*.pdf", "manual.pdf.html"));
+        assert!(glob_match("img-??.png", "img-01.png"));
+        assert!(glob_match("report-[0-9]*.csv", "report-2024.csv"));
+        assert!(!glob_match("report-[!0-9]*.csv", "report-2024.csv"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+    }
+
+    #[test]
+    fn test_accept_and_reject() {
+        let filter = UrlFilter {
+            accept: vec!["pdf".to_string(), "*.tar.gz".to_string()],
+            reject: vec!["draft-*".to_string()],
+            ..UrlFilter::default()
+        };
+        assert!(filter.check(&url("https://example.com/docs/manual.pdf")).is_ok());
+        assert!(filter.check(&url("https://example.com/release.tar.gz")).is_ok());
+        assert!(filter.check(&url("https://example.com/index.html")).is_err());
+        assert!(filter.check(&url("https://example.com/draft-1.pdf")).is_err());
+        // 目录没有文件名，不受 -A/-R 限制
+        assert!(filter.check(&url("https://example.com/docs/")).is_ok());
+        assert!(filter.without_filename_rules().check(&url("https://example.com/index.html")).is_ok());
+    }
+
+    #[test]
+    fn test_regex_rules() {
+        let filter = UrlFilter {
+            accept_regex: Some(Regex::new(r"/docs/").unwrap()),
+            reject_regex: Some(Regex::new(r"\?print=1$").unwrap()),
+            ..UrlFilter::default()
+        };
+        assert!(filter.check(&url("https://example.com/docs/a.html")).is_ok());
+        assert!(filter.check(&url("https://example.com/blog/a.html")).is_err());
+        assert!(filter.check(&url("https://example.com/docs/a.html?print=1")).is_err());
+    }
+
+    #[test]
+    fn test_domains() {
+        let filter = UrlFilter {
+            domains: vec!["example.com".to_string()],
+            exclude_domains: vec!["ads.example.com".to_string()],
+            ..UrlFilter::default()
+        };
+        assert!(filter.check(&url("https://example.com/")).is_ok());
+        assert!(filter.check(&url("https://cdn.Example.com/a.js")).is_ok());
+        assert!(filter.check(&url("https://notexample.com/")).is_err());
+        assert!(filter.check(&url("https://ads.example.com/banner.png")).is_err());
+    }
+
+    #[test]
+    fn test_directories() {
+        let filter = UrlFilter {
+            include_directories: vec!["/docs".to_string(), "/pub/*/releases".to_string()],
+            exclude_directories: vec!["/docs/old/".to_string()],
+            ..UrlFilter::default()
+        };
+        assert!(filter.check(&url("https://example.com/docs/a.html")).is_ok());
+        assert!(filter.check(&url("https://example.com/docs/guide/b.html")).is_ok());
+        assert!(filter.check(&url("https://example.com/pub/tool/releases/v1.zip")).is_ok());
+        assert!(filter.check(&url("https://example.com/docsets/a.html")).is_err());
+        assert!(filter.check(&url("https://example.com/docs/old/a.html")).is_err());
+        assert!(filter.check(&url("https://example.com/index.html")).is_err());
+    }
+}
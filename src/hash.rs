@@ -149,16 +149,18 @@ pub fn detect_hash_type(hash_value: &str) -> Option<HashType> {
     }
 }
 
-/// 显示哈希计算结果
+/// 显示哈希计算结果；写到标准错误，不混入 `-O -` 的输出，`-q` 时也显示
 pub fn display_hash_results(results: &[HashResult], file_path: &str) {
-    println!("\n文件 {} 的哈希值:", file_path);
+    eprintln!("\n文件 {} 的哈希值:", file_path);
     for result in results {
-        println!("  {}", result);
+        eprintln!("  {}", result);
     }
 }
 
-/// 验证并显示哈希比较结果
-pub fn verify_and_display(file_path: &str, expected_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// 验证并显示哈希比较结果，与 [`display_hash_results`] 一样写到标准错误
+///
+/// 计算 `file_path` 的哈希，报告中显示 `display_name`，例如校验 `.part` 时显示最终的文件名。
+pub fn verify_and_display(file_path: &str, display_name: &str, expected_hash: &str) -> Result<(), Box<dyn std::error::Error>> {
     // 自动检测哈希类型
     let hash_type = detect_hash_type(expected_hash)
         .ok_or_else(|| format!("无法识别哈希值格式: {}", expected_hash))?;
@@ -166,15 +168,15 @@ pub fn verify_and_display(file_path: &str, expected_hash: &str) -> Result<(), Bo
     let calculated = calculate_hash(file_path, &hash_type)?;
     let matches = calculated.value.to_lowercase() == expected_hash.to_lowercase();
     
-    println!("\n哈希验证结果:");
-    println!("  文件: {}", file_path);
-    println!("  算法: {}", hash_type);
-    println!("  计算值: {}", calculated.value);
-    println!("  期望值: {}", expected_hash);
-    println!("  结果: {}", if matches { "✓ 匹配" } else { "✗ 不匹配" });
+    eprintln!("\n哈希验证结果:");
+    eprintln!("  文件: {}", display_name);
+    eprintln!("  算法: {}", hash_type);
+    eprintln!("  计算值: {}", calculated.value);
+    eprintln!("  期望值: {}", expected_hash);
+    eprintln!("  结果: {}", if matches { "✓ 匹配" } else { "✗ 不匹配" });
     
     if !matches {
-        return Err("哈希验证失败".into());
+        return Err(format!("哈希验证失败: {} 为 {}，期望 {}", hash_type, calculated.value, expected_hash).into());
     }
     
     Ok(())
@@ -0,0 +1,279 @@
+use std::ops::Range;
+use regex::Regex;
+use reqwest::Url;
+
+/// 链接的用途，决定递归下载时是否跟随
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkKind {
+    /// `<base href>`，页面中相对链接的基准
+    Base,
+    /// 普通超链接，例如 `<a href>`
+    Navigation,
+    /// 显示页面所需的资源：图片、样式表、脚本、字体等
+    Requisite,
+}
+
+/// HTML 或 CSS 文档中引用的一个链接
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// 链接地址，已解码 HTML 实体，尚未解析为绝对地址
+    pub value: String,
+    pub kind: LinkKind,
+    /// 原始地址在文档中的字节范围，转换链接时替换这一段
+    pub span: Range<usize>,
+}
+
+/// 提取页面中 `href`/`src` 属性以及内嵌样式中的 `url()`，注释中的内容会被忽略
+pub fn extract_links(html: &str) -> Vec<Link> {
+    let comment_re = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    let tag_re = Regex::new(r"<([a-zA-Z][a-zA-Z0-9]*)\b([^>]*)>").unwrap();
+    let attr_re = Regex::new(r#"(?:^|\s)([a-zA-Z][a-zA-Z0-9-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap();
+    let style_re = Regex::new(r"(?is)<style\b[^>]*>(.*?)</style>").unwrap();
+
+    // 用等长的空白替换注释，保持其余内容的位置不变
+    let html = comment_re.replace_all(html, |c: &regex::Captures| " ".repeat(c[0].len()));
+
+    let mut links = Vec::new();
+    for tag in tag_re.captures_iter(&html) {
+        let tag_name = tag[1].to_ascii_lowercase();
+        let attrs_start = tag.get(2).unwrap().start();
+
+        let attrs: Vec<_> = attr_re
+            .captures_iter(&tag[2])
+            .map(|attr| {
+                let value = attr.get(2).or(attr.get(3)).or(attr.get(4)).unwrap();
+                (attr[1].to_ascii_lowercase(), value.as_str(), value.start() + attrs_start..value.end() + attrs_start)
+            })
+            .collect();
+        let rel = attrs
+            .iter()
+            .find(|(name, _, _)| name == "rel")
+            .map(|(_, value, _)| value.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        for (name, value, span) in attrs {
+            let kind = match (tag_name.as_str(), name.as_str()) {
+                ("base", "href") => LinkKind::Base,
+                ("link", "href") if rel.split_whitespace().any(|r| matches!(r, "stylesheet" | "icon" | "preload")) => {
+                    LinkKind::Requisite
+                }
+                (_, "href") => LinkKind::Navigation,
+                (_, "src") => LinkKind::Requisite,
+                (_, "style") => {
+                    links.extend(css_links_at(&decode_entities(value), span.start, value));
+                    continue;
+                }
+                _ => continue,
+            };
+            links.push(Link { value: decode_entities(value.trim()), kind, span });
+        }
+    }
+
+    for style in style_re.captures_iter(&html) {
+        let content = style.get(1).unwrap();
+        links.extend(css_links_at(content.as_str(), content.start(), content.as_str()));
+    }
+
+    links.sort_by_key(|link| link.span.start);
+    links
+}
+
+/// 页面是否通过 `<meta name="robots" content="nofollow">`（或 `none`）要求不跟随其中的链接
+pub fn meta_robots_nofollow(html: &str) -> bool {
+    let meta_re = Regex::new(r"(?i)<meta\b([^>]*)>").unwrap();
+    let attr_re = Regex::new(r#"(?:^|\s)([a-zA-Z][a-zA-Z0-9-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap();
+
+    meta_re.captures_iter(html).any(|meta| {
+        let mut name = String::new();
+        let mut content = String::new();
+        for attr in attr_re.captures_iter(&meta[1]) {
+            let value = attr.get(2).or(attr.get(3)).or(attr.get(4)).map_or("", |m| m.as_str());
+            match attr[1].to_ascii_lowercase().as_str() {
+                "name" => name = value.to_ascii_lowercase(),
+                "content" => content = value.to_ascii_lowercase(),
+                _ => {}
+            }
+        }
+        name == "robots" && content.split(',').any(|d| matches!(d.trim(), "nofollow" | "none"))
+    })
+}
+
+/// 提取 CSS 中 `url()` 和 `@import` 引用的资源
+pub fn extract_css_links(css: &str) -> Vec<Link> {
+    css_links_at(css, 0, css)
+}
+
+/// `decoded` 是 `raw` 解码实体后的内容；只有两者一致时才能准确记录位置，否则不提取
+fn css_links_at(decoded: &str, offset: usize, raw: &str) -> Vec<Link> {
+    if decoded != raw {
+        return Vec::new();
+    }
+    let url_re = Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)"#).unwrap();
+    let import_re = Regex::new(r#"(?i)@import\s+(?:"([^"]*)"|'([^']*)')"#).unwrap();
+
+    let mut links: Vec<Link> = url_re
+        .captures_iter(raw)
+        .chain(import_re.captures_iter(raw))
+        .filter_map(|c| c.get(1).or(c.get(2)).or(c.get(3)))
+        .map(|m| Link {
+            value: m.as_str().trim().to_string(),
+            kind: LinkKind::Requisite,
+            span: m.start() + offset..m.end() + offset,
+        })
+        .collect();
+    links.sort_by_key(|link| link.span.start);
+    links
+}
+
+/// 页面中相对链接的基准：第一个 `<base href>`，没有时为页面地址
+pub fn document_base(page_url: &Url, links: &[Link]) -> Url {
+    links
+        .iter()
+        .find(|link| link.kind == LinkKind::Base)
+        .and_then(|link| page_url.join(&link.value).ok())
+        .unwrap_or_else(|| page_url.clone())
+}
+
+/// 把链接解析为绝对地址，去掉片段标识；只保留 http/https 链接
+pub fn resolve(base: &Url, value: &str) -> Option<Url> {
+    if value.is_empty() || value.starts_with('#') {
+        return None;
+    }
+    let mut url = base.join(value).ok()?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return None;
+    }
+    url.set_fragment(None);
+    Some(url)
+}
+
+/// 解析页面中的所有链接，返回链接及其绝对地址
+pub fn resolve_links(page_url: &Url, links: &[Link]) -> Vec<(LinkKind, Url)> {
+    let base = document_base(page_url, links);
+    links
+        .iter()
+        .filter(|link| link.kind != LinkKind::Base)
+        .filter_map(|link| resolve(&base, &link.value).map(|url| (link.kind, url)))
+        .collect()
+}
+
+/// 按 `rewrite` 的结果替换文档中的链接，返回 None 的链接保持不变
+pub fn rewrite_links(document: &str, links: &[Link], mut rewrite: impl FnMut(&Link) -> Option<String>) -> String {
+    let mut output = String::with_capacity(document.len());
+    let mut last = 0;
+    for link in links {
+        if link.span.start < last {
+            continue;
+        }
+        if let Some(replacement) = rewrite(link) {
+            output.push_str(&document[last..link.span.start]);
+            output.push_str(&replacement);
+            last = link.span.end;
+        }
+    }
+    output.push_str(&document[last..]);
+    output
+}
+
+/// 解码属性值中常见的 HTML 实体
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(links: &[Link]) -> Vec<(LinkKind, &str)> {
+        links.iter().map(|l| (l.kind, l.value.as_str())).collect()
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let html = r#"<html><head><link rel="stylesheet" href='style.css'><link rel=next href="page2.html"></head>
+            <body><A HREF=page.html>Page</A>
+            <img class="logo" src="img/logo.png" alt="logo">
+            <!-- <a href="hidden.html">hidden</a> -->
+            <a href="search?q=1&amp;page=2">Search</a></body></html>"#;
+        let links = extract_links(html);
+        assert_eq!(values(&links), vec![
+            (LinkKind::Requisite, "style.css"),
+            (LinkKind::Navigation, "page2.html"),
+            (LinkKind::Navigation, "page.html"),
+            (LinkKind::Requisite, "img/logo.png"),
+            (LinkKind::Navigation, "search?q=1&page=2"),
+        ]);
+        assert_eq!(&html[links[2].span.clone()], "page.html");
+    }
+
+    #[test]
+    fn test_extract_inline_css_links() {
+        let html = r#"<style>body { background: url("bg.png"); }</style><div style="background: url(tile.gif)"></div>"#;
+        let links = extract_links(html);
+        assert_eq!(values(&links), vec![(LinkKind::Requisite, "bg.png"), (LinkKind::Requisite, "tile.gif")]);
+        assert_eq!(&html[links[1].span.clone()], "tile.gif");
+    }
+
+    #[test]
+    fn test_extract_css_links() {
+        let css = r#"@import "reset.css"; @font-face { src: url('fonts/a.woff2') format("woff2"); } .x { background: url( img/x.png ) }"#;
+        let links = extract_css_links(css);
+        assert_eq!(values(&links), vec![
+            (LinkKind::Requisite, "reset.css"),
+            (LinkKind::Requisite, "fonts/a.woff2"),
+            (LinkKind::Requisite, "img/x.png"),
+        ]);
+    }
+
+    #[test]
+    fn test_meta_robots_nofollow() {
+        assert!(meta_robots_nofollow(r#"<meta name="robots" content="noindex, nofollow">"#));
+        assert!(meta_robots_nofollow(r#"<META CONTENT=none NAME=ROBOTS>"#));
+        assert!(!meta_robots_nofollow(r#"<meta name="robots" content="index, follow">"#));
+        assert!(!meta_robots_nofollow(r#"<meta name="description" content="nofollow">"#));
+    }
+
+    #[test]
+    fn test_resolve_links() {
+        let page = Url::parse("https://example.com/docs/guide/index.html").unwrap();
+        let links = extract_links(r##"
+            <a href="intro.html#top">Intro</a>
+            <a href="../api/">API</a>
+            <a href="/about">About</a>
+            <a href="#section">Section</a>
+            <a href="mailto:admin@example.com">Mail</a>
+            <a href="https://other.example.org/x">Other</a>"##);
+        let urls: Vec<String> = resolve_links(&page, &links).into_iter().map(|(_, url)| url.into()).collect();
+        assert_eq!(urls, vec![
+            "https://example.com/docs/guide/intro.html",
+            "https://example.com/docs/api/",
+            "https://example.com/about",
+            "https://other.example.org/x",
+        ]);
+    }
+
+    #[test]
+    fn test_resolve_links_with_base() {
+        let page = Url::parse("https://example.com/a/b.html").unwrap();
+        let links = extract_links(r#"<base href="https://cdn.example.com/static/"><img src="logo.png">"#);
+        let urls = resolve_links(&page, &links);
+        assert_eq!(urls, vec![(LinkKind::Requisite, Url::parse("https://cdn.example.com/static/logo.png").unwrap())]);
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        let html = r#"<a href="a.html">A</a><img src="b.png">"#;
+        let links = extract_links(html);
+        let rewritten = rewrite_links(html, &links, |link| (link.value == "b.png").then(|| "img/b.png".to_string()));
+        assert_eq!(rewritten, r#"<a href="a.html">A</a><img src="img/b.png">"#);
+    }
+}
@@ -1,50 +1,222 @@
+mod auth;
+mod batch;
 mod cli;
+mod console;
+mod control;
+mod cookies;
+mod convert;
 mod download;
+mod filter;
 mod hash;
+mod html;
+mod paths;
+mod proxy;
+mod ratelimit;
+mod recursive;
+mod retry;
+mod robots;
+mod scheduler;
+mod spider;
+#[cfg(test)]
+mod test_server;
 
-use cli::parse_args;
-use download::download_file;
-use hash::{calculate_all_hashes, display_hash_results, verify_and_display};
+use auth::{take_url_credentials, Authenticator, Netrc};
+use batch::{display_summary, download_all, read_url_list, BatchResult};
+use cli::{parse_args, Args, Command};
+use cookies::CookieJar;
+use download::{download_file, DownloadOptions, ExistingFile, RequestSpec};
+use filter::UrlFilter;
+use hash::{calculate_all_hashes, detect_hash_type, display_hash_results};
+use indicatif::MultiProgress;
+use regex::Regex;
+use reqwest::{Method, Url};
+use reqwest::header::{HeaderMap, HeaderValue, REFERER};
+use paths::PathLayout;
+use proxy::ProxyConfig;
+use ratelimit::RateLimiter;
+use recursive::{crawl, CrawlOptions};
+use retry::RetryPolicy;
+use spider::{check_all, display_report, LinkState};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-fn main() {
-    let mut args = parse_args();
+#[tokio::main]
+async fn main() {
+    let args = parse_args();
+    console::set_quiet(args.quiet);
+
+    let mut urls = args.urls.clone();
+    if let Some(path) = &args.input_file {
+        match read_url_list(path) {
+            Ok(list) => urls.extend(list),
+            Err(e) => {
+                eprintln!("读取 URL 列表 {} 失败: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if urls.is_empty() {
+        eprintln!("没有需要下载的 URL");
+        std::process::exit(1);
+    }
+    if urls.len() > 1 && args.output.is_some() {
+        eprintln!("下载多个 URL 时不能使用 -O 指定输出文件名");
+        std::process::exit(1);
+    }
+    // -p 不加 -r 时只下载页面本身及其所需资源
+    let crawling = args.recursive || args.mirror || args.page_requisites;
+    if crawling && args.output.is_some() {
+        eprintln!("递归下载时不能使用 -O 指定输出文件名");
+        std::process::exit(1);
+    }
+    // 只有一个期望的哈希值，下载多个文件时会把其余文件都当作校验失败而删除
+    if args.verify_hash.is_some() && (urls.len() > 1 || args.input_file.is_some() || crawling) {
+        eprintln!("--verify-hash 只能用于下载单个 URL，不能与多个 URL、-i、-r、-m 或 -p 一起使用");
+        std::process::exit(1);
+    }
+    // 写到标准输出的数据无法续传、比较时间戳，也无法事后计算哈希
+    if args.output.as_deref() == Some("-")
+        && (args.continue_ || args.timestamping || args.hash || args.verify_hash.is_some())
+    {
+        eprintln!("-O - 不能与 -c、-N、--hash 或 --verify-hash 一起使用");
+        std::process::exit(1);
+    }
 
     // 如果没有协议，自动补全为 https://
-    if !args.url.starts_with("http://") && !args.url.starts_with("https://") {
-        args.url = format!("https://{}", args.url);
+    for url in &mut urls {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            *url = format!("https://{}", url);
+        }
     }
+    let auth = authenticator(&args, &mut urls);
     
-    // 执行下载
-    if let Err(e) = download_file(&args.url, &args.output, args.threads, args.continue_) {
-        eprintln!("下载失败: {}", e);
+    // 在开始下载前检查哈希值格式，避免下载完才发现参数有误
+    if let Some(expected_hash) = &args.verify_hash
+        && detect_hash_type(expected_hash).is_none()
+    {
+        eprintln!("无法识别哈希值格式: {}", expected_hash);
         std::process::exit(1);
     }
-    
-    // 确定下载的文件名
-    let filename = match &args.output {
-        Some(name) => name.clone(),
-        None => {
-            // 从URL推断文件名（与download.rs中的逻辑保持一致）
-            args.url.split('/')
-                .last()
-                .filter(|s| !s.is_empty())
-                .unwrap_or("output")
-                .to_string()
+
+    let filter = UrlFilter {
+        accept: args.accept.clone(),
+        reject: args.reject.clone(),
+        accept_regex: args.accept_regex.as_deref().map(compile_regex),
+        reject_regex: args.reject_regex.as_deref().map(compile_regex),
+        domains: args.domains.clone(),
+        exclude_domains: args.exclude_domains.clone(),
+        include_directories: args.include_directories.clone(),
+        exclude_directories: args.exclude_directories.clone(),
+    };
+
+    let options = DownloadOptions {
+        output: args.output.clone(),
+        threads: args.threads,
+        continue_download: args.continue_,
+        retry: RetryPolicy {
+            tries: args.tries,
+            wait: Duration::from_secs(args.retry_wait),
+            max_wait: Duration::from_secs(args.max_retry_wait),
+        },
+        min_split_size: args.min_split_size,
+        strict_resume: args.strict_resume,
+        verify_hash: args.verify_hash.clone(),
+        file_allocation: args.file_allocation,
+        rate_limiter: args.limit_rate
+            .filter(|&rate| rate > 0)
+            .map(|rate| Arc::new(RateLimiter::new(rate))),
+        progress: None,
+        timestamping: args.timestamping || args.mirror,
+        filter,
+        layout: PathLayout {
+            prefix: args.directory_prefix.as_ref().map(PathBuf::from),
+            force_directories: args.force_directories,
+            no_host_directories: args.no_host_directories,
+            cut_dirs: args.cut_dirs,
+        },
+        user_agent: args.user_agent.clone(),
+        headers: request_headers(&args),
+        request: request_spec(&args),
+        cookies: load_cookies(&args),
+        auth: Some(Arc::new(auth)),
+        proxy: proxy_config(&args),
+        reserved_names: Arc::default(),
+        existing_file: if args.no_clobber {
+            ExistingFile::NoClobber
+        } else if args.backups > 0 {
+            ExistingFile::Backups(args.backups)
+        } else {
+            ExistingFile::Number
+        },
+    };
+
+    // 自定义请求只发送一次，无法续传，也不适合用于递归下载和链接检查
+    if options.request.is_some() && (crawling || args.spider || args.continue_) {
+        eprintln!("--method、--post-data 等选项不能与 -r、-m、-p、--spider 或 -c 一起使用");
+        std::process::exit(1);
+    }
+
+    // --spider 只检查链接，不保存文件
+    if args.spider {
+        let results = check_all(&urls, &options, args.max_concurrent_downloads).await;
+        display_report(&results);
+        save_cookies(&args, options.cookies.as_deref());
+        if results.iter().any(|r| r.state() == LinkState::Broken) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let cookie_jar = options.cookies.clone();
+
+    // 执行下载：单个 URL 直接下载，多个 URL 或递归下载按 -j 并发下载并汇总结果
+    if args.convert_links && !crawling {
+        eprintln!("警告: -k 需要与 -r、-m 或 -p 一起使用，已忽略");
+    }
+    let (filenames, all_succeeded) = if crawling {
+        // 镜像模式不限制递归深度
+        let max_depth = if args.mirror {
+            None
+        } else if args.recursive {
+            (args.level > 0).then_some(args.level)
+        } else {
+            Some(0)
+        };
+        let crawl_options = CrawlOptions {
+            max_depth,
+            no_parent: args.no_parent,
+            span_hosts: args.span_hosts,
+            page_requisites: args.page_requisites,
+            convert_links: args.convert_links,
+            jobs: args.max_concurrent_downloads,
+            // 默认遵守，多次 -e robots=... 以最后一次为准
+            robots: args.execute.iter().rev().map(|command| match command {
+                Command::Robots(on) => *on,
+            }).next().unwrap_or(true),
+        };
+        let options = DownloadOptions { progress: Some(MultiProgress::new()), ..options };
+        summarize(crawl(&urls, &crawl_options, &options).await)
+    } else if urls.len() == 1 && args.input_file.is_none() {
+        match download_file(&urls[0], &options).await {
+            Ok(filename) => (vec![filename], true),
+            Err(e) => {
+                eprintln!("下载失败: {}", e);
+                (Vec::new(), false)
+            }
         }
+    } else {
+        let options = DownloadOptions { progress: Some(MultiProgress::new()), ..options };
+        summarize(download_all(&urls, &options, args.max_concurrent_downloads).await)
     };
     
-    // 处理哈希相关功能
-    if args.hash || args.verify_hash.is_some() {
-        if let Some(expected_hash) = args.verify_hash {
-            // 验证哈希值
-            if let Err(e) = verify_and_display(&filename, &expected_hash) {
-                eprintln!("哈希验证失败: {}", e);
-                std::process::exit(1);
-            }
-        } else if args.hash {
-            // 计算并显示所有哈希值
-            match calculate_all_hashes(&filename) {
-                Ok(results) => display_hash_results(&results, &filename),
+    save_cookies(&args, cookie_jar.as_deref());
+
+    // 计算并显示所有哈希值（--verify-hash 已在下载完成、重命名之前校验）
+    if args.hash {
+        for filename in &filenames {
+            match calculate_all_hashes(filename) {
+                Ok(results) => display_hash_results(&results, filename),
                 Err(e) => {
                     eprintln!("哈希计算失败: {}", e);
                     std::process::exit(1);
@@ -52,4 +224,147 @@ fn main() {
             }
         }
     }
-}
\ No newline at end of file
+
+    if !all_succeeded {
+        std::process::exit(1);
+    }
+}
+
+/// 打印汇总信息（-q 时不打印），返回成功下载的文件名以及是否全部成功
+fn summarize(results: Vec<BatchResult>) -> (Vec<String>, bool) {
+    if !console::is_quiet() {
+        display_summary(&results);
+    }
+    let all_succeeded = results.iter().all(|r| r.outcome.is_ok());
+    let filenames = results.into_iter().filter_map(|r| r.outcome.ok()).collect();
+    (filenames, all_succeeded)
+}
+
+/// 由 --header 和 --referer 组成的请求头，--header 中的同名请求头优先
+fn request_headers(args: &Args) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &args.header {
+        headers.append(name.clone(), value.clone());
+    }
+    if let Some(referer) = &args.referer
+        && !headers.contains_key(REFERER)
+    {
+        match HeaderValue::from_str(referer) {
+            Ok(value) => {
+                headers.insert(REFERER, value);
+            }
+            Err(_) => {
+                eprintln!("无效的 Referer: {}", referer);
+                std::process::exit(1);
+            }
+        }
+    }
+    headers
+}
+
+/// 由 --method、--post-data 等组成的自定义请求；普通的 GET 请求返回 None
+fn request_spec(args: &Args) -> Option<RequestSpec> {
+    let read_body = |path: &String| {
+        std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("读取请求体文件 {} 失败: {}", path, e);
+            std::process::exit(1);
+        })
+    };
+    let body = match (&args.post_data, &args.post_file, &args.body_data, &args.body_file) {
+        (Some(data), _, _, _) | (_, _, Some(data), _) => Some(data.clone().into_bytes()),
+        (_, Some(path), _, _) | (_, _, _, Some(path)) => Some(read_body(path)),
+        _ => None,
+    };
+    let method = match &args.method {
+        Some(method) => method.clone(),
+        None if body.is_some() => Method::POST,
+        None => return None,
+    };
+    (method != Method::GET || body.is_some()).then_some(RequestSpec { method, body })
+}
+
+/// 需要读取或保存 cookie 时创建共享的 cookie 存储
+fn load_cookies(args: &Args) -> Option<Arc<CookieJar>> {
+    match &args.load_cookies {
+        Some(path) => match CookieJar::load(path) {
+            Ok(jar) => Some(Arc::new(jar)),
+            Err(e) => {
+                eprintln!("读取 cookie 文件 {} 失败: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => args.save_cookies.as_ref().map(|_| Arc::new(CookieJar::default())),
+    }
+}
+
+/// --save-cookies 时保存下载过程中收到的 cookie
+fn save_cookies(args: &Args, jar: Option<&CookieJar>) {
+    if let (Some(path), Some(jar)) = (&args.save_cookies, jar)
+        && let Err(e) = jar.save(path, args.keep_session_cookies)
+    {
+        eprintln!("保存 cookie 文件 {} 失败: {}", path, e);
+    }
+}
+
+/// 取出 URL 中的用户名和密码，与 --user/--password 一起按 URL 的主机记录；~/.netrc 按主机查找
+fn authenticator(args: &Args, urls: &mut [String]) -> Authenticator {
+    let password = if args.ask_password {
+        let url_user = urls.iter().filter_map(|url| Url::parse(url).ok()).find(|url| !url.username().is_empty());
+        let Some(user) = args.user.clone().or_else(|| url_user.map(|url| url.username().to_string())) else {
+            eprintln!("--ask-password 需要用 --user 或在 URL 中指定用户名");
+            std::process::exit(1);
+        };
+        match rpassword::prompt_password(format!("用户 {} 的密码: ", user)) {
+            Ok(password) => Some(password),
+            Err(e) => {
+                eprintln!("读取密码失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        args.password.clone()
+    };
+
+    let mut auth = Authenticator::new(Netrc::load_default());
+    for url in urls.iter_mut() {
+        let Ok(mut parsed) = Url::parse(url) else {
+            continue;
+        };
+        // 用户名和密码不出现在日志和保存的文件名中；--ask-password 输入的密码优先于 URL 中的密码
+        let (user, url_password) = match take_url_credentials(&mut parsed) {
+            Some((user, url_password)) => {
+                *url = parsed.to_string();
+                (Some(user), url_password.filter(|_| !args.ask_password))
+            }
+            None => (args.user.clone(), None),
+        };
+        if let Some(user) = user {
+            auth.add(&parsed, user, url_password.or_else(|| password.clone()));
+        }
+    }
+    auth
+}
+
+/// 由环境变量和 --proxy 等选项组成的代理设置；--no-proxy 时直接连接
+fn proxy_config(args: &Args) -> ProxyConfig {
+    if args.no_proxy {
+        return ProxyConfig::default();
+    }
+    let mut config = ProxyConfig::from_env().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(proxy) = &args.proxy {
+        config.set_all(proxy.clone());
+    }
+    config.set_credentials(args.proxy_user.as_deref(), args.proxy_password.as_deref());
+    config
+}
+
+/// 编译命令行中的正则表达式，格式错误时退出
+fn compile_regex(pattern: &str) -> Regex {
+    Regex::new(pattern).unwrap_or_else(|e| {
+        eprintln!("无效的正则表达式 {}: {}", pattern, e);
+        std::process::exit(1);
+    })
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+use reqwest::Url;
+
+/// 目录形式的 URL（以 `/` 结尾）保存时使用的文件名
+const INDEX_FILENAME: &str = "index.html";
+
+/// 服务器给出的文件名不能使用时的替代名称，与 URL 中没有文件名时相同
+const FALLBACK_FILENAME: &str = "output";
+
+/// 下载的文件保存在本地的位置
+#[derive(Debug, Clone, Default)]
+pub struct PathLayout {
+    /// 所有文件都保存在这个目录下（-P）
+    pub prefix: Option<PathBuf>,
+    /// 非递归下载时也按 `主机名/路径` 创建目录（-x）
+    pub force_directories: bool,
+    /// 不创建主机名目录（-nH）
+    pub no_host_directories: bool,
+    /// 忽略 URL 路径中的前几级目录（--cut-dirs）
+    pub cut_dirs: usize,
+}
+
+impl PathLayout {
+    /// 把 URL 映射为本地路径 `主机名/路径`，递归下载时按网站的目录结构保存文件
+    ///
+    /// 以 `/` 结尾的 URL 保存为目录下的 `index.html`，查询字符串以 `?` 连接在文件名后面。
+    pub fn local_path(&self, url: &Url) -> PathBuf {
+        let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+        let filename = segments.last().copied().unwrap_or("");
+
+        let mut filename = if filename.is_empty() {
+            INDEX_FILENAME.to_string()
+        } else {
+            decode_segment(filename)
+        };
+        if let Some(query) = url.query() {
+            filename.push('?');
+            filename.push_str(&query.replace('/', "%2F"));
+        }
+        self.place(url, &filename)
+    }
+
+    /// 单个文件的保存路径，文件名由服务器或 URL 决定；`-x` 时放在 URL 对应的目录下
+    ///
+    /// 文件名可能来自 `Content-Disposition`，只保留最后一个路径组成部分，不会保存到目标目录之外。
+    pub fn file_path(&self, url: &Url, filename: &str) -> PathBuf {
+        let filename = safe_filename(filename);
+        let filename = filename.as_str();
+        if self.force_directories {
+            return self.place(url, filename);
+        }
+        match &self.prefix {
+            Some(prefix) => prefix.join(filename),
+            None => PathBuf::from(filename),
+        }
+    }
+
+    /// 按 `-P`、`-nH` 和 `--cut-dirs` 组合出 URL 所在目录，再加上文件名
+    fn place(&self, url: &Url, filename: &str) -> PathBuf {
+        let mut path = self.prefix.clone().unwrap_or_default();
+        if !self.no_host_directories {
+            path.push(host_directory(url));
+        }
+
+        let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+        let dirs = segments.split_last().map_or(&[][..], |(_, dirs)| dirs);
+        for dir in dirs.iter().filter(|dir| !dir.is_empty()).skip(self.cut_dirs) {
+            path.push(decode_segment(dir));
+        }
+        path.push(filename);
+        path
+    }
+}
+
+/// 主机名目录，非默认端口附加在后面
+fn host_directory(url: &Url) -> String {
+    let host = url.host_str().unwrap_or("localhost");
+    match url.port() {
+        Some(port) if cfg!(windows) => format!("{}+{}", host, port),
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+/// 去掉目录、根目录和 `..`，只保留文件名本身
+fn safe_filename(filename: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or("").replace('\0', "");
+    match name.trim() {
+        "" | "." | ".." => FALLBACK_FILENAME.to_string(),
+        _ => name,
+    }
+}
+
+/// 解码路径段中的 `%XX`；解码结果会跳出当前目录或不是合法 UTF-8 时保持原样
+fn decode_segment(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            decoded.push(u8::from_str_radix(&segment[i + 1..i + 3], 16).unwrap());
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(decoded) if !matches!(decoded.as_str(), "." | "..") && !decoded.contains(['/', '\\', '\0']) => decoded,
+        _ => segment.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(url: &str) -> PathBuf {
+        PathLayout::default().local_path(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_local_path_for() {
+        assert_eq!(local("https://example.com/"), PathBuf::from("example.com/index.html"));
+        assert_eq!(local("https://example.com"), PathBuf::from("example.com/index.html"));
+        assert_eq!(local("https://example.com/docs/"), PathBuf::from("example.com/docs/index.html"));
+        assert_eq!(local("https://example.com/docs/a.html"), PathBuf::from("example.com/docs/a.html"));
+        assert_eq!(local("https://example.com/search?q=a/b"), PathBuf::from("example.com/search?q=a%2Fb"));
+    }
+
+    #[test]
+    fn test_local_path_with_port() {
+        let expected = if cfg!(windows) { "example.com+8080/a.txt" } else { "example.com:8080/a.txt" };
+        assert_eq!(local("http://example.com:8080/a.txt"), PathBuf::from(expected));
+        assert_eq!(local("http://example.com:80/a.txt"), PathBuf::from("example.com/a.txt"));
+    }
+
+    #[test]
+    fn test_layout_options() {
+        let url = Url::parse("https://example.com/pub/tools/v1/tool.tar.gz").unwrap();
+        let layout = PathLayout {
+            prefix: Some(PathBuf::from("downloads")),
+            no_host_directories: true,
+            cut_dirs: 1,
+            ..PathLayout::default()
+        };
+        assert_eq!(layout.local_path(&url), PathBuf::from("downloads/tools/v1/tool.tar.gz"));
+        // 去掉的目录多于实际目录时直接保存在前缀目录下
+        let layout = PathLayout { cut_dirs: 5, ..layout };
+        assert_eq!(layout.local_path(&url), PathBuf::from("downloads/tool.tar.gz"));
+    }
+
+    #[test]
+    fn test_file_path() {
+        let url = Url::parse("https://example.com/pub/tool.tar.gz").unwrap();
+        assert_eq!(PathLayout::default().file_path(&url, "tool.tar.gz"), PathBuf::from("tool.tar.gz"));
+        let layout = PathLayout { prefix: Some(PathBuf::from("out")), ..PathLayout::default() };
+        assert_eq!(layout.file_path(&url, "tool.tar.gz"), PathBuf::from("out/tool.tar.gz"));
+        let layout = PathLayout { force_directories: true, ..layout };
+        assert_eq!(layout.file_path(&url, "renamed.tgz"), PathBuf::from("out/example.com/pub/renamed.tgz"));
+    }
+
+    #[test]
+    fn test_file_path_stays_in_prefix() {
+        let url = Url::parse("https://example.com/download?id=1").unwrap();
+        let layout = PathLayout { prefix: Some(PathBuf::from("out")), ..PathLayout::default() };
+        assert_eq!(layout.file_path(&url, "/etc/passwd"), PathBuf::from("out/passwd"));
+        assert_eq!(layout.file_path(&url, "../../.bashrc"), PathBuf::from("out/.bashrc"));
+        assert_eq!(layout.file_path(&url, "..\\..\\evil.exe"), PathBuf::from("out/evil.exe"));
+        assert_eq!(layout.file_path(&url, ".."), PathBuf::from("out/output"));
+        assert_eq!(layout.file_path(&url, "dir/"), PathBuf::from("out/output"));
+    }
+
+    #[test]
+    fn test_decode_segment() {
+        assert_eq!(decode_segment("my%20file.txt"), "my file.txt");
+        assert_eq!(decode_segment("%E4%B8%AD%E6%96%87"), "中文");
+        // 不能解码出路径分隔符或上级目录
+        assert_eq!(decode_segment("a%2Fb"), "a%2Fb");
+        assert_eq!(decode_segment("%2e%2e"), "%2e%2e");
+        assert_eq!(decode_segment("100%"), "100%");
+    }
+}
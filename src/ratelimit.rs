@@ -0,0 +1,86 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 令牌桶限速器
+///
+/// 所有下载任务（以及批量下载中的所有文件）共享同一个实例，
+/// 因此限制的是总速率而不是单个连接的速率。
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_second: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// 当前可用的令牌数，为负表示已经透支，需要等待补足
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        RateLimiter {
+            bytes_per_second: bytes_per_second.max(1),
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// 消耗 `bytes` 个令牌，返回调用方需要等待的时间
+    fn reserve(&self, bytes: u64) -> Duration {
+        let rate = self.bytes_per_second as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+
+        // 按经过的时间补充令牌，最多积攒一秒的额度，避免空闲后突发
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.last_refill = now;
+
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+
+    /// 读取了 `bytes` 字节后调用，超出限额时让出执行权等待
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_accumulates_debt() {
+        let limiter = RateLimiter::new(1000);
+        // 桶初始为空，第一次读取就需要等待
+        let first = limiter.reserve(500);
+        assert!(first > Duration::from_millis(400) && first <= Duration::from_millis(500));
+
+        // 后续读取继续累积透支
+        let second = limiter.reserve(500);
+        assert!(second > Duration::from_millis(900) && second <= Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_limits_rate() {
+        let limiter = RateLimiter::new(100 * 1024);
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire(10 * 1024).await;
+        }
+        // 40 KiB 在 100 KiB/s 的限制下至少需要约 0.4 秒
+        assert!(start.elapsed() >= Duration::from_millis(350));
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use reqwest::Url;
+use tokio::task::JoinSet;
+use crate::batch::BatchResult;
+use crate::console::info;
+use crate::convert::{convert_document, DocumentKind};
+use crate::download::{create_client, download_resource, user_agent, Downloaded, DownloadOptions};
+use crate::filter::UrlFilter;
+use crate::html::{extract_css_links, extract_links, meta_robots_nofollow, resolve, resolve_links, LinkKind};
+use crate::paths::PathLayout;
+use crate::robots::RobotsCache;
+
+/// 递归下载的范围限制
+#[derive(Debug, Clone)]
+pub struct CrawlOptions {
+    /// 最大递归深度，None 表示不限制
+    pub max_depth: Option<u32>,
+    /// 不进入起始 URL 所在目录的上级目录，页面所需的资源除外
+    pub no_parent: bool,
+    /// 允许跟随指向其他主机的链接
+    pub span_hosts: bool,
+    /// 不受深度限制，下载显示页面所需的图片、样式表、脚本和字体
+    pub page_requisites: bool,
+    /// 下载完成后把 HTML/CSS 中的链接改写为指向本地副本
+    pub convert_links: bool,
+    /// 同时下载的文件数
+    pub jobs: u32,
+    /// 遵守 robots.txt（包括 Crawl-delay）和页面中的 `<meta name="robots" content="nofollow">`
+    pub robots: bool,
+}
+
+/// 由起始 URL 决定的抓取范围
+struct Scope {
+    hosts: HashSet<String>,
+    /// 起始 URL 所在的目录，`--no-parent` 时只下载这些目录下的文件
+    directories: Vec<Url>,
+    no_parent: bool,
+    span_hosts: bool,
+}
+
+impl Scope {
+    fn new<'a>(start_urls: impl Iterator<Item = &'a Url>, crawl: &CrawlOptions) -> Self {
+        let mut hosts = HashSet::new();
+        let mut directories = Vec::new();
+        for url in start_urls {
+            hosts.insert(host_key(url));
+            // 去掉最后一个 `/` 之后的文件名，得到所在目录
+            if let Ok(directory) = url.join("./") {
+                directories.push(directory);
+            }
+        }
+        Scope {
+            hosts,
+            directories,
+            no_parent: crawl.no_parent,
+            span_hosts: crawl.span_hosts,
+        }
+    }
+
+    /// 与 wget 一致，页面所需的资源不受 `--no-parent` 限制，只检查主机
+    fn contains(&self, url: &Url, kind: LinkKind) -> bool {
+        let host = host_key(url);
+        if !self.span_hosts && !self.hosts.contains(&host) {
+            return false;
+        }
+        if !self.no_parent || kind == LinkKind::Requisite {
+            return true;
+        }
+
+        // 只限制起始主机；跨主机时其他主机上的路径不受影响
+        let mut same_host = self.directories.iter().filter(|dir| host_key(dir) == host).peekable();
+        same_host.peek().is_none() || same_host.any(|dir| url.path().starts_with(dir.path()))
+    }
+}
+
+/// 用于比较的主机名和端口
+fn host_key(url: &Url) -> String {
+    format!("{}:{}", url.host_str().unwrap_or(""), url.port_or_known_default().unwrap_or(0))
+}
+
+/// 从起始 URL 开始按广度优先下载，并跟随 HTML 页面中范围内的链接
+///
+/// 每个文件按 [`PathLayout::local_path`] 映射到本地目录树中，最多同时下载 `crawl.jobs` 个文件。
+/// 与 wget 一致，robots.txt 只限制递归中发现的链接，命令行给出的起始 URL 总是会下载。
+pub async fn crawl(start_urls: &[String], crawl: &CrawlOptions, options: &DownloadOptions) -> Vec<BatchResult> {
+    let mut results = Vec::new();
+    let mut queue = VecDeque::new();
+    let mut seen = HashSet::new();
+    // 不同的 URL 可能映射到同一个本地文件（例如 `/` 和 `/index.html`），只下载一次
+    let mut claimed = HashSet::new();
+    // 成功下载的文件，以及转换链接时需要处理的文档
+    let mut downloaded_paths = HashSet::new();
+    let mut documents = Vec::new();
+
+    for url in start_urls {
+        match Url::parse(url) {
+            Ok(mut url) => {
+                url.set_fragment(None);
+                match admit(&options.filter, &url, LinkKind::Navigation) {
+                    Ok(delete_after) => {
+                        if seen.insert(url.to_string()) && claimed.insert(options.layout.local_path(&url)) {
+                            queue.push_back((url, 0, delete_after));
+                        }
+                    }
+                    Err(reason) => results.push(BatchResult {
+                        url: url.to_string(),
+                        outcome: Err(format!("被过滤规则排除: {}", reason)),
+                    }),
+                }
+            }
+            Err(e) => results.push(BatchResult {
+                url: url.clone(),
+                outcome: Err(format!("无效的 URL: {}", e)),
+            }),
+        }
+    }
+    let scope = Scope::new(queue.iter().map(|(url, _, _)| url), crawl);
+
+    let mut robots = match crawl.robots.then(|| create_client(options)) {
+        Some(Ok(client)) => Some(RobotsCache::new(client, &user_agent(options))),
+        Some(Err(e)) => {
+            eprintln!("无法创建获取 robots.txt 的客户端，将不检查 robots.txt: {}", e);
+            None
+        }
+        None => None,
+    };
+    // 每个主机下一次请求最早可以开始的时间，用于遵守 Crawl-delay
+    let mut next_request: HashMap<String, Instant> = HashMap::new();
+
+    let mut tasks = JoinSet::new();
+    loop {
+        while tasks.len() < crawl.jobs.max(1) as usize
+            && let Some((url, depth, delete_after)) = queue.pop_front()
+        {
+            let mut options = options.clone();
+            if delete_after {
+                options.filter = options.filter.without_filename_rules();
+            }
+            let mut start_at = None;
+            if let Some(robots) = robots.as_mut()
+                && let Some(delay) = robots.rules_for(&url).await.crawl_delay
+            {
+                let now = Instant::now();
+                let slot = next_request.get(&host_key(&url)).copied().filter(|t| *t > now).unwrap_or(now);
+                next_request.insert(host_key(&url), slot + delay);
+                start_at = Some(slot);
+                // 分段下载会同时向主机发出多个请求，Crawl-delay 要求每个文件只用一个连接
+                options.threads = 1;
+            }
+            tasks.spawn(async move {
+                if let Some(start_at) = start_at {
+                    tokio::time::sleep_until(start_at.into()).await;
+                }
+                let outcome = fetch(&url, &options).await;
+                (url, depth, delete_after, outcome)
+            });
+        }
+
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        let Ok((url, depth, delete_after, outcome)) = joined else {
+            continue;
+        };
+
+        match outcome {
+            Ok(downloaded) => {
+                seen.insert(downloaded.final_url.clone());
+
+                // 超过深度限制后只跟随页面所需的资源（-p），不再跟随普通链接
+                let follow_navigation = crawl.max_depth.is_none_or(|max| depth < max);
+                let follow_requisites = follow_navigation || crawl.page_requisites;
+                let (links, document) = document_links(&downloaded, robots.is_some());
+                for (kind, link) in links {
+                    let follow = match kind {
+                        LinkKind::Navigation => follow_navigation,
+                        LinkKind::Requisite => follow_requisites,
+                        LinkKind::Base => false,
+                    };
+                    if !follow || !scope.contains(&link, kind) {
+                        continue;
+                    }
+                    if let Some(robots) = robots.as_mut()
+                        && !robots.rules_for(&link).await.is_allowed(&link)
+                    {
+                        continue;
+                    }
+                    if let Ok(delete_after) = admit(&options.filter, &link, kind)
+                        && seen.insert(link.to_string())
+                        && claimed.insert(options.layout.local_path(&link))
+                    {
+                        queue.push_back((link, depth + 1, delete_after));
+                    }
+                }
+
+                if delete_after {
+                    info!("删除 {}：不符合 -A/-R 规则，仅用于查找链接", downloaded.filename);
+                    std::fs::remove_file(&downloaded.filename).ok();
+                    continue;
+                }
+                downloaded_paths.insert(PathBuf::from(&downloaded.filename));
+                documents.extend(document);
+                results.push(BatchResult { url: url.to_string(), outcome: Ok(downloaded.filename) });
+            }
+            Err(e) => {
+                eprintln!("{} 下载失败: {}", url, e);
+                results.push(BatchResult { url: url.to_string(), outcome: Err(e) });
+            }
+        }
+    }
+
+    if crawl.convert_links {
+        convert_all(&documents, &downloaded_paths, &options.layout);
+    }
+
+    results
+}
+
+/// 把所有已下载文档中的链接改写为指向本地副本
+fn convert_all(documents: &[Document], downloaded_paths: &HashSet<PathBuf>, layout: &PathLayout) {
+    let mut converted = 0;
+    for (url, path, kind) in documents {
+        let local_path = |target: &Url| Some(layout.local_path(target)).filter(|p| downloaded_paths.contains(p));
+        match convert_document(path, url, *kind, local_path) {
+            Ok(true) => converted += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!("转换 {} 中的链接失败: {}", path.display(), e),
+        }
+    }
+    info!("已转换 {} 个文件中的链接", converted);
+}
+
+/// 按过滤规则决定是否下载链接，返回是否需要在解析链接后删除
+///
+/// 与 wget 一致，被 `-A`/`-R` 排除的 HTML 页面仍然会下载以便继续查找链接，之后再删除。
+fn admit(filter: &UrlFilter, url: &Url, kind: LinkKind) -> Result<bool, String> {
+    filter.check_location(url)?;
+    match filter.check_filename(url) {
+        Ok(()) => Ok(false),
+        Err(_) if kind == LinkKind::Navigation && looks_like_html(url) => Ok(true),
+        Err(reason) => Err(reason),
+    }
+}
+
+/// 根据扩展名猜测 URL 是否为 HTML 页面
+fn looks_like_html(url: &Url) -> bool {
+    let filename = url.path_segments().and_then(|mut s| s.next_back()).unwrap_or("");
+    match filename.rsplit_once('.') {
+        Some((_, ext)) => matches!(
+            ext.to_ascii_lowercase().as_str(),
+            "html" | "htm" | "shtml" | "xhtml" | "php" | "asp" | "aspx" | "jsp"
+        ),
+        None => true,
+    }
+}
+
+/// 下载到 URL 对应的本地路径
+async fn fetch(url: &Url, options: &DownloadOptions) -> Result<Downloaded, String> {
+    let options = DownloadOptions {
+        output: Some(options.layout.local_path(url).to_string_lossy().into_owned()),
+        ..options.clone()
+    };
+    download_resource(url.as_str(), &options).await.map_err(|e| e.to_string())
+}
+
+/// 需要转换链接的文档：地址、本地路径和类型
+type Document = (Url, PathBuf, DocumentKind);
+
+/// 读取已下载的 HTML 页面或样式表，返回其中的链接以及文档信息
+///
+/// `respect_nofollow` 时，带有 `<meta name="robots" content="nofollow">` 的页面只返回页面所需的资源。
+fn document_links(downloaded: &Downloaded, respect_nofollow: bool) -> (Vec<(LinkKind, Url)>, Option<Document>) {
+    let kind = if downloaded.is_html() {
+        DocumentKind::Html
+    } else if downloaded.is_css() {
+        DocumentKind::Css
+    } else {
+        return (Vec::new(), None);
+    };
+    let Ok(url) = Url::parse(&downloaded.final_url) else {
+        return (Vec::new(), None);
+    };
+    let path = Path::new(&downloaded.filename);
+    let content = match std::fs::read(path) {
+        Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+        Err(e) => {
+            eprintln!("读取 {} 失败: {}", downloaded.filename, e);
+            return (Vec::new(), None);
+        }
+    };
+
+    let links = match kind {
+        DocumentKind::Html => {
+            let nofollow = respect_nofollow && meta_robots_nofollow(&content);
+            resolve_links(&url, &extract_links(&content))
+                .into_iter()
+                .filter(|(kind, _)| !(nofollow && *kind == LinkKind::Navigation))
+                .collect()
+        }
+        DocumentKind::Css => extract_css_links(&content)
+            .iter()
+            .filter_map(|link| resolve(&url, &link.value).map(|target| (link.kind, target)))
+            .collect(),
+    };
+    (links, Some((url, path.to_path_buf(), kind)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::RetryPolicy;
+    use crate::test_server::{Route, TestServer};
+
+    fn scope(start: &str, no_parent: bool, span_hosts: bool) -> Scope {
+        let crawl = CrawlOptions {
+            max_depth: None,
+            no_parent,
+            span_hosts,
+            page_requisites: false,
+            convert_links: false,
+            jobs: 1,
+            robots: true,
+        };
+        Scope::new([Url::parse(start).unwrap()].iter(), &crawl)
+    }
+
+    fn url(value: &str) -> Url {
+        Url::parse(value).unwrap()
+    }
+
+    #[test]
+    fn test_scope_stays_on_start_host() {
+        let scope = scope("https://example.com/docs/index.html", false, false);
+        assert!(scope.contains(&url("https://example.com/other/page.html"), LinkKind::Navigation));
+        assert!(scope.contains(&url("https://example.com:443/docs/"), LinkKind::Navigation));
+        assert!(!scope.contains(&url("https://cdn.example.com/logo.png"), LinkKind::Navigation));
+        assert!(!scope.contains(&url("https://example.com:8443/docs/"), LinkKind::Navigation));
+    }
+
+    #[test]
+    fn test_scope_span_hosts() {
+        let scope = scope("https://example.com/docs/", false, true);
+        assert!(scope.contains(&url("https://cdn.example.com/logo.png"), LinkKind::Navigation));
+    }
+
+    #[test]
+    fn test_admit() {
+        let filter = UrlFilter {
+            accept: vec!["pdf".to_string()],
+            exclude_directories: vec!["/private".to_string()],
+            ..UrlFilter::default()
+        };
+        assert_eq!(admit(&filter, &url("https://example.com/a.pdf"), LinkKind::Navigation), Ok(false));
+        // 被 -A 排除的页面下载后删除，其他文件直接跳过
+        assert_eq!(admit(&filter, &url("https://example.com/docs/"), LinkKind::Navigation), Ok(false));
+        assert_eq!(admit(&filter, &url("https://example.com/list.html"), LinkKind::Navigation), Ok(true));
+        assert!(admit(&filter, &url("https://example.com/list.html"), LinkKind::Requisite).is_err());
+        assert!(admit(&filter, &url("https://example.com/logo.png"), LinkKind::Navigation).is_err());
+        assert!(admit(&filter, &url("https://example.com/private/a.pdf"), LinkKind::Navigation).is_err());
+    }
+
+    #[test]
+    fn test_scope_no_parent() {
+        let scope = scope("https://example.com/docs/guide/index.html", true, true);
+        assert!(scope.contains(&url("https://example.com/docs/guide/intro.html"), LinkKind::Navigation));
+        assert!(scope.contains(&url("https://example.com/docs/guide/sub/page.html"), LinkKind::Navigation));
+        assert!(!scope.contains(&url("https://example.com/docs/"), LinkKind::Navigation));
+        assert!(!scope.contains(&url("https://example.com/index.html"), LinkKind::Navigation));
+        // 其他主机不受 --no-parent 限制
+        assert!(scope.contains(&url("https://cdn.example.com/assets/app.js"), LinkKind::Navigation));
+    }
+
+    #[test]
+    fn test_scope_no_parent_allows_requisites() {
+        let scope = scope("https://example.com/docs/guide/index.html", true, false);
+        assert!(scope.contains(&url("https://example.com/static/style.css"), LinkKind::Requisite));
+        assert!(scope.contains(&url("https://example.com/images/logo.png"), LinkKind::Requisite));
+        assert!(!scope.contains(&url("https://example.com/static/page.html"), LinkKind::Navigation));
+        // 仍然只下载起始主机上的资源
+        assert!(!scope.contains(&url("https://cdn.example.com/logo.png"), LinkKind::Requisite));
+    }
+
+    #[tokio::test]
+    async fn test_crawl_delay_uses_one_connection() {
+        let body: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        let server = TestServer::start(vec![
+            ("/robots.txt", Route::file("User-agent: *\nCrawl-delay: 0.01\n")),
+            ("/big.bin", Route::file(body.clone())),
+        ]).await;
+
+        let dir = PathBuf::from("/tmp/test_wget_rs_crawl_delay");
+        std::fs::remove_dir_all(&dir).ok();
+        let crawl_options = CrawlOptions {
+            max_depth: Some(1),
+            no_parent: false,
+            span_hosts: false,
+            page_requisites: false,
+            convert_links: false,
+            jobs: 1,
+            robots: true,
+        };
+        let options = DownloadOptions {
+            threads: 4,
+            min_split_size: 1024,
+            retry: RetryPolicy { tries: 1, ..RetryPolicy::default() },
+            layout: PathLayout { prefix: Some(dir.clone()), ..PathLayout::default() },
+            ..DownloadOptions::default()
+        };
+        let results = crawl(&[server.url("/big.bin")], &crawl_options, &options).await;
+
+        let saved = results.into_iter().next().unwrap().outcome.unwrap();
+        assert_eq!(std::fs::read(&saved).unwrap(), body);
+        let requests: Vec<_> = server.requests().into_iter().filter(|r| r.path == "/big.bin").collect();
+        assert!(requests.iter().all(|r| r.range.is_none()), "{:?}", requests);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+/// 失败重试策略：每次重试前等待的时间按指数增长，直到达到上限
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// 总尝试次数（包含第一次请求）
+    pub tries: u32,
+    /// 第一次重试前的等待时间
+    pub wait: Duration,
+    /// 等待时间上限
+    pub max_wait: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            tries: 5,
+            wait: Duration::from_secs(1),
+            max_wait: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 `attempt` 次尝试失败后是否还能继续重试
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.tries
+    }
+
+    /// 第 `attempt` 次尝试失败后、下一次尝试前的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.wait.saturating_mul(factor).min(self.max_wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_delay() {
+        let policy = RetryPolicy {
+            tries: 10,
+            wait: Duration::from_secs(1),
+            max_wait: Duration::from_secs(10),
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(4), Duration::from_secs(8));
+        assert_eq!(policy.delay(5), Duration::from_secs(10));
+        assert_eq!(policy.delay(64), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy { tries: 3, ..RetryPolicy::default() };
+        assert!(policy.should_retry(1));
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+
+        let no_retry = RetryPolicy { tries: 1, ..RetryPolicy::default() };
+        assert!(!no_retry.should_retry(1));
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use regex::Regex;
+use reqwest::{Client, Url};
+
+/// robots.txt 中的一条 Allow/Disallow 规则
+#[derive(Debug, Clone)]
+struct Rule {
+    /// 原始路径模式，规则冲突时较长的模式优先
+    pattern: String,
+    regex: Regex,
+    allow: bool,
+}
+
+/// 某个主机的 robots.txt 中适用于本程序的规则
+#[derive(Debug, Clone, Default)]
+pub struct RobotsRules {
+    rules: Vec<Rule>,
+    /// 两次请求之间需要等待的时间
+    pub crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    /// 按 robots.txt 的规则判断是否允许抓取；多条规则匹配时取最长的模式，长度相同时 Allow 优先
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        let target = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        self.rules
+            .iter()
+            .filter(|rule| rule.regex.is_match(&target))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// User-Agent 中的产品名，例如 `Wget/0.1.0 (linux)` 中的 `wget`
+fn agent_token(user_agent: &str) -> String {
+    user_agent
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// 解析 robots.txt，取出与 `user_agent` 匹配的分组；没有专门的分组时使用 `*` 分组
+pub fn parse_robots(content: &str, user_agent: &str) -> RobotsRules {
+    struct Group {
+        agents: Vec<String>,
+        rules: Vec<Rule>,
+        crawl_delay: Option<Duration>,
+    }
+
+    let mut groups: Vec<Group> = Vec::new();
+    // 连续的 User-agent 行属于同一个分组，出现规则后再遇到 User-agent 则开始新分组
+    let mut in_rules = true;
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+
+        if key == "user-agent" {
+            if in_rules {
+                groups.push(Group { agents: Vec::new(), rules: Vec::new(), crawl_delay: None });
+                in_rules = false;
+            }
+            if let Some(group) = groups.last_mut() {
+                group.agents.push(agent_token(value));
+            }
+            continue;
+        }
+
+        in_rules = true;
+        let Some(group) = groups.last_mut() else {
+            continue;
+        };
+        match key.as_str() {
+            // 空的 Disallow 表示不做限制
+            "allow" | "disallow" if !value.is_empty() => {
+                if let Some(regex) = pattern_regex(value) {
+                    group.rules.push(Rule { pattern: value.to_string(), regex, allow: key == "allow" });
+                }
+            }
+            "crawl-delay" => {
+                group.crawl_delay = value.parse::<f64>().ok().filter(|d| d.is_finite() && *d >= 0.0).map(Duration::from_secs_f64);
+            }
+            _ => {}
+        }
+    }
+
+    let token = agent_token(user_agent);
+    let specific: Vec<&Group> = groups.iter().filter(|g| g.agents.contains(&token)).collect();
+    let selected = if specific.is_empty() {
+        groups.iter().filter(|g| g.agents.iter().any(|a| a == "*")).collect()
+    } else {
+        specific
+    };
+
+    RobotsRules {
+        rules: selected.iter().flat_map(|g| g.rules.iter().cloned()).collect(),
+        crawl_delay: selected.iter().filter_map(|g| g.crawl_delay).max(),
+    }
+}
+
+/// 把路径模式转换为正则表达式：`*` 匹配任意字符，结尾的 `$` 表示必须匹配到末尾
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let body: Vec<String> = pattern.split('*').map(regex::escape).collect();
+    let mut regex = format!("^{}", body.join(".*"));
+    if anchored {
+        regex.push('$');
+    }
+    Regex::new(&regex).ok()
+}
+
+/// 按主机缓存 robots.txt，每个主机只请求一次
+pub struct RobotsCache {
+    client: Client,
+    user_agent: String,
+    hosts: HashMap<String, RobotsRules>,
+}
+
+impl RobotsCache {
+    pub fn new(client: Client, user_agent: &str) -> Self {
+        RobotsCache {
+            client,
+            user_agent: user_agent.to_string(),
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// 获取 URL 所在主机的规则，第一次访问该主机时下载 robots.txt
+    pub async fn rules_for(&mut self, url: &Url) -> &RobotsRules {
+        let origin = url.origin().ascii_serialization();
+        if !self.hosts.contains_key(&origin) {
+            let rules = self.fetch(url).await;
+            self.hosts.insert(origin.clone(), rules);
+        }
+        &self.hosts[&origin]
+    }
+
+    /// robots.txt 不存在或无法获取时不做任何限制
+    async fn fetch(&self, url: &Url) -> RobotsRules {
+        let Ok(robots_url) = url.join("/robots.txt") else {
+            return RobotsRules::default();
+        };
+        match self.client.get(robots_url).send().await {
+            Ok(response) if response.status().is_success() => match response.text().await {
+                Ok(content) => parse_robots(&content, &self.user_agent),
+                Err(_) => RobotsRules::default(),
+            },
+            _ => RobotsRules::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_AGENT: &str = "Wget/0.1.0 (linux)";
+
+    fn allowed(rules: &RobotsRules, path: &str) -> bool {
+        rules.is_allowed(&Url::parse(&format!("https://example.com{}", path)).unwrap())
+    }
+
+    #[test]
+    fn test_agent_token() {
+        assert_eq!(agent_token(USER_AGENT), "wget");
+        assert_eq!(agent_token("Googlebot"), "googlebot");
+    }
+
+    #[test]
+    fn test_default_group() {
+        let rules = parse_robots("User-agent: *\nDisallow: /private/\nDisallow: /tmp\n", USER_AGENT);
+        assert!(allowed(&rules, "/index.html"));
+        assert!(!allowed(&rules, "/private/a.html"));
+        assert!(!allowed(&rules, "/tmp/x"));
+        assert!(!allowed(&rules, "/tmpfile"));
+        assert_eq!(rules.crawl_delay, None);
+    }
+
+    #[test]
+    fn test_specific_group_overrides_default() {
+        let content = "\
+# 所有爬虫
+User-agent: *
+Disallow: /
+
+User-agent: Googlebot
+User-agent: Wget
+Disallow: /admin
+Crawl-delay: 1.5
+";
+        let rules = parse_robots(content, USER_AGENT);
+        assert!(allowed(&rules, "/docs/"));
+        assert!(!allowed(&rules, "/admin/login"));
+        assert_eq!(rules.crawl_delay, Some(Duration::from_millis(1500)));
+
+        let other = parse_robots(content, "curl/8.0");
+        assert!(!allowed(&other, "/docs/"));
+    }
+
+    #[test]
+    fn test_allow_and_wildcards() {
+        let content = "User-agent: *\nDisallow: /docs/\nAllow: /docs/public/\nDisallow: /*.pdf$\nDisallow: /search?\nDisallow:\n";
+        let rules = parse_robots(content, USER_AGENT);
+        assert!(!allowed(&rules, "/docs/internal.html"));
+        assert!(allowed(&rules, "/docs/public/index.html"));
+        assert!(!allowed(&rules, "/files/manual.pdf"));
+        assert!(allowed(&rules, "/files/manual.pdf.html"));
+        assert!(!allowed(&rules, "/search?q=1"));
+        assert!(allowed(&rules, "/search"));
+    }
+
+    #[test]
+    fn test_empty_robots_allows_everything() {
+        let rules = parse_robots("", USER_AGENT);
+        assert!(allowed(&rules, "/anything"));
+    }
+}
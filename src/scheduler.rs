@@ -0,0 +1,179 @@
+use crate::control::{ControlFile, Segment};
+
+/// 拆分区段时每一半至少保留的字节数下限，保证拆分点远离正在写入的位置
+const MIN_SPLIT_FLOOR: u64 = 64 * 1024;
+
+/// 多线程下载的区段调度器
+///
+/// 空闲线程优先领取尚未开始的区段；没有可领取的区段时，
+/// 将剩余字节最多的活动区段从中间拆开，把后半段交给空闲线程（类似 aria2）。
+#[derive(Debug)]
+pub struct Scheduler {
+    control: ControlFile,
+    active: Vec<bool>,
+    failed: Vec<bool>,
+    aborted: bool,
+    min_split_size: u64,
+}
+
+impl Scheduler {
+    pub fn new(control: ControlFile, min_split_size: u64) -> Self {
+        let count = control.segments.len();
+        Scheduler {
+            control,
+            active: vec![false; count],
+            failed: vec![false; count],
+            aborted: false,
+            min_split_size: min_split_size.max(MIN_SPLIT_FLOOR),
+        }
+    }
+
+    pub fn control(&self) -> &ControlFile {
+        &self.control
+    }
+
+    pub fn segment(&self, index: usize) -> &Segment {
+        &self.control.segments[index]
+    }
+
+    /// 记录区段新写入的字节数
+    pub fn record(&mut self, index: usize, bytes: u64) {
+        self.control.segments[index].downloaded += bytes;
+    }
+
+    /// 停止分配新的区段，正在下载的线程也应尽快退出
+    pub fn abort(&mut self) {
+        self.aborted = true;
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// 为空闲线程分配一个区段，返回其下标；没有可做的工作时返回 None
+    pub fn next_segment(&mut self) -> Option<usize> {
+        if self.aborted {
+            return None;
+        }
+
+        let pending = (0..self.control.segments.len()).find(|&i| {
+            !self.active[i] && !self.failed[i] && !self.control.segments[i].is_complete()
+        });
+        if let Some(index) = pending {
+            self.active[index] = true;
+            return Some(index);
+        }
+
+        self.split_largest()
+    }
+
+    /// 线程结束对区段的处理；失败的区段不会再分配给其他线程
+    pub fn release(&mut self, index: usize, failed: bool) {
+        self.active[index] = false;
+        if failed {
+            self.failed[index] = true;
+        }
+    }
+
+    fn split_largest(&mut self) -> Option<usize> {
+        let (victim, remaining) = self
+            .control
+            .segments
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.active[*i])
+            .map(|(i, segment)| (i, segment.len() - segment.downloaded))
+            .max_by_key(|(_, remaining)| *remaining)?;
+
+        if remaining < 2 * self.min_split_size {
+            return None;
+        }
+
+        let segment = &mut self.control.segments[victim];
+        let split_at = segment.position() + remaining / 2;
+        let new_segment = Segment::new(split_at, segment.end);
+        segment.end = split_at - 1;
+
+        self.control.segments.push(new_segment);
+        self.active.push(true);
+        self.failed.push(false);
+        Some(self.control.segments.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::plan_segments;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn scheduler(total_size: u64, threads: u32) -> Scheduler {
+        let control = ControlFile {
+            url: "https://example.com/file.iso".to_string(),
+            total_size,
+            etag: None,
+            last_modified: None,
+            segments: plan_segments(total_size, threads),
+        };
+        Scheduler::new(control, MB)
+    }
+
+    #[test]
+    fn test_assigns_pending_segments_first() {
+        let mut scheduler = scheduler(8 * MB, 2);
+        assert_eq!(scheduler.next_segment(), Some(0));
+        assert_eq!(scheduler.next_segment(), Some(1));
+    }
+
+    #[test]
+    fn test_splits_largest_remaining_segment() {
+        let mut scheduler = scheduler(8 * MB, 2);
+        scheduler.next_segment();
+        scheduler.next_segment();
+
+        // 区段 0 已经完成一半，区段 1 还没有进展
+        scheduler.record(0, 2 * MB);
+        let index = scheduler.next_segment().unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(scheduler.segment(1).end, 6 * MB - 1);
+        assert_eq!(scheduler.segment(2).start, 6 * MB);
+        assert_eq!(scheduler.segment(2).end, 8 * MB - 1);
+
+        let total: u64 = scheduler.control().segments.iter().map(Segment::len).sum();
+        assert_eq!(total, 8 * MB);
+    }
+
+    #[test]
+    fn test_does_not_split_below_minimum() {
+        let mut scheduler = scheduler(3 * MB, 2);
+        scheduler.next_segment();
+        scheduler.next_segment();
+        assert_eq!(scheduler.next_segment(), None);
+    }
+
+    #[test]
+    fn test_failed_segment_is_not_reassigned() {
+        let mut scheduler = scheduler(MB, 1);
+        assert_eq!(scheduler.next_segment(), Some(0));
+        scheduler.release(0, true);
+        assert_eq!(scheduler.next_segment(), None);
+    }
+
+    #[test]
+    fn test_abort_stops_assignment() {
+        let mut scheduler = scheduler(8 * MB, 2);
+        assert_eq!(scheduler.next_segment(), Some(0));
+        scheduler.abort();
+        assert!(scheduler.is_aborted());
+        assert_eq!(scheduler.next_segment(), None);
+    }
+
+    #[test]
+    fn test_released_segment_is_reassigned() {
+        let mut scheduler = scheduler(MB, 1);
+        assert_eq!(scheduler.next_segment(), Some(0));
+        scheduler.release(0, false);
+        assert_eq!(scheduler.next_segment(), Some(0));
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::{Client, StatusCode, Url};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use crate::auth::{send, Authenticator};
+use crate::console::info;
+use crate::download::{create_client, is_retryable_status, DownloadOptions};
+
+/// `--spider` 模式下对一个 URL 的检查结果
+#[derive(Debug)]
+pub struct SpiderResult {
+    pub url: String,
+    /// 请求成功时为服务器的响应，网络错误等无法得到响应时为错误信息
+    pub outcome: Result<Response, String>,
+}
+
+/// 跟随重定向之后的最终响应
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub final_url: String,
+    /// 是否经过了重定向
+    pub redirected: bool,
+    /// 服务器给出的 Content-Length
+    pub size: Option<u64>,
+}
+
+/// 链接的状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkState {
+    Alive,
+    Redirected,
+    Broken,
+}
+
+impl SpiderResult {
+    pub fn state(&self) -> LinkState {
+        match &self.outcome {
+            Ok(response) if !response.status.is_success() => LinkState::Broken,
+            Ok(response) if response.redirected => LinkState::Redirected,
+            Ok(_) => LinkState::Alive,
+            Err(_) => LinkState::Broken,
+        }
+    }
+}
+
+/// 检查所有 URL 是否可以访问，不保存任何文件；同时最多检查 `jobs` 个，结果按输入顺序返回
+pub async fn check_all(urls: &[String], options: &DownloadOptions, jobs: u32) -> Vec<SpiderResult> {
+    let client = match create_client(options) {
+        Ok(client) => client,
+        Err(e) => {
+            return urls
+                .iter()
+                .map(|url| SpiderResult { url: url.clone(), outcome: Err(e.to_string()) })
+                .collect();
+        }
+    };
+
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1) as usize));
+    let mut tasks = JoinSet::new();
+    for (index, url) in urls.iter().enumerate() {
+        let semaphore = semaphore.clone();
+        let client = client.clone();
+        let url = url.clone();
+        let options = options.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire().await.expect("semaphore closed");
+            (index, check_url(&client, &url, &options).await)
+        });
+    }
+
+    let mut outcomes: Vec<Option<Result<Response, String>>> = (0..urls.len()).map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((index, outcome)) = joined {
+            outcomes[index] = Some(outcome);
+        }
+    }
+
+    urls.iter()
+        .zip(outcomes)
+        .map(|(url, outcome)| SpiderResult {
+            url: url.clone(),
+            outcome: outcome.unwrap_or_else(|| Err("检查任务异常退出".to_string())),
+        })
+        .collect()
+}
+
+/// 用 HEAD 请求检查 URL；服务器不支持 HEAD 时改用 GET，只读取响应头
+async fn check_url(client: &Client, url: &str, options: &DownloadOptions) -> Result<Response, String> {
+    let parsed_url = Url::parse(url).map_err(|e| format!("无效的 URL: {}", e))?;
+    options.filter.check(&parsed_url).map_err(|reason| format!("被过滤规则排除: {}", reason))?;
+
+    let retry = &options.retry;
+    let mut attempt = 1;
+    loop {
+        let error = match request(client, &parsed_url, options.auth.as_deref()).await {
+            Ok(response) if !is_retryable_status(response.status) => return Ok(response),
+            Ok(response) => format!("服务器返回 {}", response.status),
+            Err(e) => e.to_string(),
+        };
+        if !retry.should_retry(attempt) {
+            return Err(error);
+        }
+        let delay = retry.delay(attempt);
+        info!("检查 {} 出错: {}，{} 秒后重试 ({}/{})", url, error, delay.as_secs_f32(), attempt, retry.tries - 1);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+async fn request(client: &Client, url: &Url, auth: Option<&Authenticator>) -> Result<Response, reqwest::Error> {
+    let mut response = send(client.head(url.clone()), auth).await?;
+    if matches!(response.status(), StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) {
+        response = send(client.get(url.clone()), auth).await?;
+    }
+    // HEAD 响应没有响应体，不能用 `content_length()`
+    let size = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    Ok(Response {
+        status: response.status(),
+        final_url: response.url().to_string(),
+        redirected: response.url() != url,
+        size,
+    })
+}
+
+/// 打印每个 URL 的检查结果
+pub fn display_report(results: &[SpiderResult]) {
+    let count = |state| results.iter().filter(|r| r.state() == state).count();
+    println!(
+        "\n链接检查: 共 {} 个，正常 {} 个，重定向 {} 个，失效 {} 个",
+        results.len(),
+        count(LinkState::Alive),
+        count(LinkState::Redirected),
+        count(LinkState::Broken)
+    );
+    for result in results {
+        let label = match result.state() {
+            LinkState::Alive => "正常",
+            LinkState::Redirected => "重定向",
+            LinkState::Broken => "失效",
+        };
+        match &result.outcome {
+            Ok(response) => {
+                let size = response.size.map_or("未知".to_string(), |size| format!("{} 字节", size));
+                let reason = response.status.canonical_reason().unwrap_or("");
+                if response.redirected {
+                    println!(
+                        "  [{}] {} -> {} {} {}，大小: {}",
+                        label, result.url, response.final_url, response.status.as_u16(), reason, size
+                    );
+                } else {
+                    println!("  [{}] {} {} {}，大小: {}", label, result.url, response.status.as_u16(), reason, size);
+                }
+            }
+            Err(e) => println!("  [{}] {}: {}", label, result.url, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::RetryPolicy;
+    use crate::test_server::closed_url;
+
+    fn result(url: &str, status: StatusCode, final_url: &str) -> SpiderResult {
+        SpiderResult {
+            url: url.to_string(),
+            outcome: Ok(Response { status, final_url: final_url.to_string(), redirected: url != final_url, size: None }),
+        }
+    }
+
+    #[test]
+    fn test_link_state() {
+        let url = "https://example.com/a";
+        assert_eq!(result(url, StatusCode::OK, url).state(), LinkState::Alive);
+        assert_eq!(result(url, StatusCode::OK, "https://example.com/b").state(), LinkState::Redirected);
+        assert_eq!(result(url, StatusCode::NOT_FOUND, "https://example.com/b").state(), LinkState::Broken);
+        let failed = SpiderResult { url: url.to_string(), outcome: Err("连接被拒绝".to_string()) };
+        assert_eq!(failed.state(), LinkState::Broken);
+    }
+
+    #[tokio::test]
+    async fn test_check_all_reports_unreachable() {
+        let options = DownloadOptions {
+            retry: RetryPolicy { tries: 1, ..RetryPolicy::default() },
+            ..DownloadOptions::default()
+        };
+        let urls = vec![closed_url("/missing"), "not a url".to_string()];
+        let results = check_all(&urls, &options, 2).await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.state() == LinkState::Broken));
+        assert!(results[1].outcome.as_ref().unwrap_err().contains("无效的 URL"));
+    }
+}
@@ -0,0 +1,182 @@
+//! 单元测试使用的本地 HTTP 服务器，每个连接只处理一个请求
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 一个路径对应的响应
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    /// 为 0 时返回 200，支持 Range 请求
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// 发送响应体之前等待的时间
+    pub delay: Duration,
+    /// 要求的 Authorization 请求头，不符时返回 401 和 Basic 质询
+    pub authorization: Option<String>,
+}
+
+impl Route {
+    pub fn file(body: impl Into<Vec<u8>>) -> Route {
+        Route { body: body.into(), ..Route::default() }
+    }
+
+    pub fn redirect(status: u16, location: &str) -> Route {
+        Route { status, headers: vec![("Location".to_string(), location.to_string())], ..Route::default() }
+    }
+
+    pub fn authorization(mut self, value: &str) -> Route {
+        self.authorization = Some(value.to_string());
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Route {
+        self.delay = delay;
+        self
+    }
+}
+
+/// 服务器收到的请求
+#[derive(Debug, Clone)]
+pub struct Logged {
+    pub method: String,
+    pub path: String,
+    pub range: Option<String>,
+    pub authorization: Option<String>,
+}
+
+pub struct TestServer {
+    port: u16,
+    log: Arc<Mutex<Vec<Logged>>>,
+}
+
+impl TestServer {
+    pub async fn start(routes: Vec<(&str, Route)>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let routes: Arc<HashMap<String, Route>> = Arc::new(routes.into_iter().map(|(p, r)| (p.to_string(), r)).collect());
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let server_log = log.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (routes, log) = (routes.clone(), server_log.clone());
+                tokio::spawn(async move {
+                    serve(stream, &routes, &log).await.ok();
+                });
+            }
+        });
+        TestServer { port, log }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    pub fn requests(&self) -> Vec<Logged> {
+        self.log.lock().unwrap().clone()
+    }
+}
+
+async fn serve(mut stream: TcpStream, routes: &HashMap<String, Route>, log: &Mutex<Vec<Logged>>) -> std::io::Result<()> {
+    let mut buffer = Vec::new();
+    let head_end = loop {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let path = request_line.next().unwrap_or("").to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+
+    // 读完请求体，避免关闭连接时客户端收到 RST
+    let body_len: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    while buffer.len() < head_end + body_len {
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let range = headers.get("range").cloned();
+    let authorization = headers.get("authorization").cloned();
+    log.lock().unwrap().push(Logged {
+        method: method.clone(),
+        path: path.clone(),
+        range: range.clone(),
+        authorization: authorization.clone(),
+    });
+
+    let Some(route) = routes.get(&path) else {
+        return respond(&mut stream, 404, &[], b"", true).await;
+    };
+    if let Some(expected) = &route.authorization
+        && authorization.as_ref() != Some(expected)
+    {
+        let challenge = [("WWW-Authenticate".to_string(), "Basic realm=\"test\"".to_string())];
+        return respond(&mut stream, 401, &challenge, b"", true).await;
+    }
+    tokio::time::sleep(route.delay).await;
+
+    let send_body = method != "HEAD";
+    if route.status != 0 {
+        return respond(&mut stream, route.status, &route.headers, &route.body, send_body).await;
+    }
+
+    let total = route.body.len();
+    let mut response_headers = route.headers.clone();
+    response_headers.push(("Accept-Ranges".to_string(), "bytes".to_string()));
+    let span = range
+        .as_deref()
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(start, end)| {
+            let last = total.checked_sub(1)?;
+            let start: usize = start.parse().ok()?;
+            let end = if end.is_empty() { last } else { end.parse::<usize>().ok()?.min(last) };
+            (start <= end).then_some((start, end))
+        });
+    match span {
+        Some((start, end)) => {
+            response_headers.push(("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end, total)));
+            respond(&mut stream, 206, &response_headers, &route.body[start..=end], send_body).await
+        }
+        None => respond(&mut stream, 200, &response_headers, &route.body, send_body).await,
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: u16, headers: &[(String, String)], body: &[u8], send_body: bool) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} Test\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    if send_body {
+        stream.write_all(body).await?;
+    }
+    stream.shutdown().await
+}
+
+/// 刚刚释放的本机端口上的 URL，连接会被拒绝
+pub fn closed_url(path: &str) -> String {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    format!("http://127.0.0.1:{}{}", port, path)
+}